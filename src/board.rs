@@ -1,16 +1,30 @@
 use std::{array::from_fn, fmt::Display, ops::{Add, Sub}};
 
-use crate::{game::{CastlingRights, Move, MoveError, MoveType, Player}, pieces::{Piece, PieceType}};
+use crate::{game::{CastlingRights, Move, MoveError, MoveType, Player}, pieces::{Piece, PieceType}, zobrist};
 
 const B_SQUARE: char = '▓';
 const W_SQUARE: char = '░';
 
-#[derive(Clone, Copy, PartialEq)]
+pub(crate) const KNIGHT_OFFSETS: [(i8, i8); 8] = [(2, 1), (1, 2), (-1, 2), (-2, 1), (-2, -1), (-1, -2), (1, -2), (2, -1)];
+pub(crate) const KING_OFFSETS: [(i8, i8); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+pub(crate) const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+pub(crate) const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black
 }
 
+impl Color {
+    pub fn opponent(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
 impl Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
@@ -29,10 +43,26 @@ impl From<Color> for char {
     }
 }
 
+// Everything a move overwrites, so that it can be unmade exactly
+#[derive(Clone, Debug)]
+pub struct MoveUndo {
+	captured: Option<(Coordinate, Piece)>,
+	can_passant: Option<Coordinate>,
+	castling_rights: (CastlingRights, CastlingRights),
+	halfmove_clock: u32,
+	fullmove_number: u32,
+	zobrist: u64,
+}
+
 #[derive(Clone)]
 pub struct Board {
     pub squares: [[Square; 8]; 8],
 	pub can_passant: Option<Coordinate>,
+	pub side_to_move: Color,
+	pub castling_rights: (CastlingRights, CastlingRights),
+	pub halfmove_clock: u32,
+	pub fullmove_number: u32,
+	zobrist: u64,
 }
 
 impl Board {
@@ -68,98 +98,362 @@ impl Board {
 	pub fn get_square_mut(&mut self, coordinate: Coordinate) -> &mut Square {
 		&mut self.squares[coordinate.rank as usize][coordinate.file as usize]
 	}
-	pub fn execute_move(&mut self, chess_move: &Move, player: &mut Player) -> Result<(), MoveError> {
-        let valid = self.validate_move(&chess_move, player);
-		if valid.is_err() {return valid;}
-		let mut piece_type = chess_move.piece_type;
-		match chess_move.move_type {
-			MoveType::Promotion => piece_type = chess_move.promotion.unwrap(),
-			_ => {}
+	pub fn castling_rights(&self, color: Color) -> CastlingRights {
+		match color {
+			Color::White => self.castling_rights.0,
+			Color::Black => self.castling_rights.1,
+		}
+	}
+	pub fn execute_move(&mut self, chess_move: &Move, player: &mut Player) -> Result<MoveUndo, MoveError> {
+		self.validate_move(chess_move, player.color)?;
+		let undo = self.apply_move(chess_move, player.color);
+		player.set_checked(false);
+		Ok(undo)
+	}
+	// Reverses the last move applied, given what applying it returned
+	pub fn unmake_move(&mut self, chess_move: &Move, undo: MoveUndo) {
+		let color = self.side_to_move.opponent();
+		let home_rank = match color {
+			Color::White => Rank::One,
+			Color::Black => Rank::Eight,
 		};
-		let (home_rank, pawn_rank, _promote_rank) = match player.color {
-			Color::White => (Rank::One, Rank::Two, Rank::Eight),
-			Color::Black => (Rank::Eight, Rank::Seven, Rank::One),
+		self.set_piece(chess_move.to, None);
+		self.set_piece(chess_move.from, Some(Piece::new(color, chess_move.piece_type)));
+		let rook_files = match chess_move.move_type {
+			MoveType::CastleKingSide => Some((File::F, File::H)),
+			MoveType::CastleQueenSide => Some((File::D, File::A)),
+			_ => None,
 		};
-		let (rank_diff, _file_diff) = (
-			(chess_move.to.rank - chess_move.from.rank),
-			(chess_move.to.file - chess_move.from.file)
-		);
-		if piece_type != PieceType::Pawn {
-			self.can_passant = None;
+		if let Some((from, to)) = rook_files {
+			self.set_piece(Coordinate { rank: home_rank, file: from }, None);
+			self.set_piece(Coordinate { rank: home_rank, file: to }, Some(Piece::new(color, PieceType::Rook)));
 		}
-		match piece_type {
-			PieceType::Pawn => {
-				if self.can_passant.is_some() {
-					let passant_pawn_coord = Coordinate {
-						rank: chess_move.from.rank,
-						file: chess_move.to.file
-					};
-					if chess_move.to == self.can_passant.unwrap() {
-						self.get_square_mut(passant_pawn_coord).piece = None;
-					}
-				}
-				if chess_move.from.rank == pawn_rank && rank_diff.abs() == 2 {
-					self.can_passant = Some(Coordinate {
-						rank: chess_move.from.rank.forward(1, player.color).unwrap(),
-						file: chess_move.from.file
-					});
-				}
-			},
-			PieceType::Bishop => {
-			},
-			PieceType::Knight => {
-			},
-			PieceType::Rook => {
-				if chess_move.from.rank == home_rank {
-					match chess_move.from.file {
-							File::A => player.remove_castling_rights(CastlingRights::Queen),
-							File::H => player.remove_castling_rights(CastlingRights::King),
-							_ => ()
-					};
-				}
-			},
-			PieceType::Queen => {
-			},
-			PieceType::King => {
-				if chess_move.from.rank == home_rank {
-					player.remove_castling_rights(CastlingRights::Both);
-				}
-			},
+		if let Some((coordinate, piece)) = undo.captured {
+			self.set_piece(coordinate, Some(piece));
+		}
+		self.side_to_move = color;
+		self.can_passant = undo.can_passant;
+		self.castling_rights = undo.castling_rights;
+		self.halfmove_clock = undo.halfmove_clock;
+		self.fullmove_number = undo.fullmove_number;
+		self.zobrist = undo.zobrist;
+	}
+	pub fn zobrist(&self) -> u64 {
+		self.zobrist
+	}
+	// Recomputes the hash, needed after editing squares or state directly rather than through moves
+	pub fn rehash(&mut self) {
+		self.zobrist = zobrist::hash(self);
+	}
+	// Keeps the hash in step with the pieces so it never has to be recomputed
+	pub(crate) fn set_piece(&mut self, coordinate: Coordinate, piece: Option<Piece>) {
+		if let Some(old) = &self.get_square(coordinate).piece {
+			self.zobrist ^= zobrist::piece_key(old, coordinate);
+		}
+		if let Some(new) = &piece {
+			self.zobrist ^= zobrist::piece_key(new, coordinate);
+		}
+		self.get_square_mut(coordinate).piece = piece;
+	}
+	pub(crate) fn apply_move(&mut self, chess_move: &Move, color: Color) -> MoveUndo {
+		let undo = MoveUndo {
+			captured: self.captured_piece(chess_move),
+			can_passant: self.can_passant,
+			castling_rights: self.castling_rights,
+			halfmove_clock: self.halfmove_clock,
+			fullmove_number: self.fullmove_number,
+			zobrist: self.zobrist,
+		};
+		self.zobrist ^= zobrist::state_key(self);
+		let mut piece_type = chess_move.piece_type;
+		if chess_move.move_type == MoveType::Promotion {
+			piece_type = chess_move.promotion.unwrap();
+		}
+		let (home_rank, pawn_rank) = match color {
+			Color::White => (Rank::One, Rank::Two),
+			Color::Black => (Rank::Eight, Rank::Seven),
+		};
+		let rank_diff = chess_move.to.rank - chess_move.from.rank;
+		let passant = self.can_passant.take();
+		if chess_move.piece_type == PieceType::Pawn || self.get_square(chess_move.to).piece.is_some() {
+			self.halfmove_clock = 0;
+		} else {
+			self.halfmove_clock += 1;
+		}
+		if color == Color::Black {
+			self.fullmove_number += 1;
+		}
+		if chess_move.piece_type == PieceType::Pawn {
+			if passant == Some(chess_move.to) {
+				let passant_pawn_coord = Coordinate {
+					rank: chess_move.from.rank,
+					file: chess_move.to.file
+				};
+				self.set_piece(passant_pawn_coord, None);
+			}
+			if chess_move.from.rank == pawn_rank && rank_diff.abs() == 2 {
+				self.can_passant = Some(Coordinate {
+					rank: chess_move.from.rank.forward(1, color).unwrap(),
+					file: chess_move.from.file
+				});
+			}
 		}
 		match chess_move.move_type {
 			MoveType::CastleKingSide => {
-				self.get_square_mut(Coordinate {
+				self.set_piece(Coordinate {
 					rank: home_rank,
 					file: File::H
-				}).piece = None;
-				self.get_square_mut(Coordinate {
+				}, None);
+				self.set_piece(Coordinate {
 					rank: home_rank,
 					file: File::F
-				}).piece = Some(Piece::new(player.color, PieceType::Rook));
+				}, Some(Piece::new(color, PieceType::Rook)));
 			},
 			MoveType::CastleQueenSide => {
-				self.get_square_mut(Coordinate {
+				self.set_piece(Coordinate {
 					rank: home_rank,
 					file: File::A
-				}).piece = None;
-				self.get_square_mut(Coordinate {
+				}, None);
+				self.set_piece(Coordinate {
 					rank: home_rank,
 					file: File::D
-				}).piece = Some(Piece::new(player.color, PieceType::Rook));
+				}, Some(Piece::new(color, PieceType::Rook)));
 			},
 			_ => {}
 		}
-		self.get_square_mut(chess_move.from).piece = None;
-		self.get_square_mut(chess_move.to).piece = Some(Piece::new(player.color, piece_type));
-		Ok(())
+		// Any move touching a king or rook home square ends castling on that side
+		for coordinate in [chess_move.from, chess_move.to] {
+			let (rights, side) = match coordinate.rank {
+				Rank::One => (&mut self.castling_rights.0, coordinate.file),
+				Rank::Eight => (&mut self.castling_rights.1, coordinate.file),
+				_ => continue,
+			};
+			match side {
+				File::A => rights.remove(CastlingRights::Queen),
+				File::E => rights.remove(CastlingRights::Both),
+				File::H => rights.remove(CastlingRights::King),
+				_ => {}
+			}
+		}
+		self.set_piece(chess_move.from, None);
+		self.set_piece(chess_move.to, Some(Piece::new(color, piece_type)));
+		self.side_to_move = color.opponent();
+		self.zobrist ^= zobrist::state_key(self);
+		undo
+	}
+	fn captured_piece(&self, chess_move: &Move) -> Option<(Coordinate, Piece)> {
+		match self.get_square(chess_move.to).piece {
+			Some(piece) => Some((chess_move.to, piece)),
+			None if chess_move.piece_type == PieceType::Pawn && self.can_passant == Some(chess_move.to) => {
+				let coordinate = Coordinate { rank: chess_move.from.rank, file: chess_move.to.file };
+				self.get_square(coordinate).piece.map(|piece| (coordinate, piece))
+			},
+			None => None,
+		}
+	}
+	pub fn king_coordinate(&self, color: Color) -> Option<Coordinate> {
+		self.squares.iter().flatten().find(|square| {
+			matches!(square.piece, Some(Piece { color: piece_color, piece_type: PieceType::King }) if piece_color == color)
+		}).map(|square| square.coordinate)
+	}
+	pub fn is_in_check(&self, color: Color) -> bool {
+		match self.king_coordinate(color) {
+			Some(coordinate) => self.is_attacked(coordinate, color.opponent()),
+			None => false,
+		}
+	}
+	pub fn is_attacked(&self, coordinate: Coordinate, by: Color) -> bool {
+		let holds = |rank_diff: i8, file_diff: i8, piece_types: &[PieceType]| {
+			match coordinate.offset(rank_diff, file_diff) {
+				Some(coord) => matches!(&self.get_square(coord).piece,
+					Some(piece) if piece.color == by && piece_types.contains(&piece.piece_type)),
+				None => false,
+			}
+		};
+		// Pawns attack towards the opponent, so look back along the attacker's direction
+		let pawn_rank_diff = match by {
+			Color::White => -1,
+			Color::Black => 1,
+		};
+		if holds(pawn_rank_diff, -1, &[PieceType::Pawn]) || holds(pawn_rank_diff, 1, &[PieceType::Pawn]) {
+			return true;
+		}
+		if KNIGHT_OFFSETS.iter().any(|&(rank_diff, file_diff)| holds(rank_diff, file_diff, &[PieceType::Knight])) {
+			return true;
+		}
+		if KING_OFFSETS.iter().any(|&(rank_diff, file_diff)| holds(rank_diff, file_diff, &[PieceType::King])) {
+			return true;
+		}
+		let slides = |directions: &[(i8, i8)], piece_types: &[PieceType]| {
+			directions.iter().any(|&(rank_step, file_step)| {
+				let mut current = coordinate;
+				while let Some(next) = current.offset(rank_step, file_step) {
+					if let Some(piece) = &self.get_square(next).piece {
+						return piece.color == by && piece_types.contains(&piece.piece_type);
+					}
+					current = next;
+				}
+				false
+			})
+		};
+		slides(&ROOK_DIRECTIONS, &[PieceType::Rook, PieceType::Queen])
+			|| slides(&BISHOP_DIRECTIONS, &[PieceType::Bishop, PieceType::Queen])
+	}
+	pub fn legal_moves(&self, color: Color) -> Vec<Move> {
+		self.candidate_moves(color).into_iter()
+			.filter(|candidate| self.validate_move(candidate, color).is_ok())
+			.collect()
 	}
-	fn validate_move(&self, chess_move: &Move, player: &Player) -> Result<(), MoveError> {
+	pub fn has_legal_move(&self, color: Color) -> bool {
+		self.candidate_moves(color).iter()
+			.any(|candidate| self.validate_move(candidate, color).is_ok())
+	}
+	// Dead positions where neither side can ever mate: K v K, K+minor v K and bishops all on one square color
+	pub fn is_insufficient_material(&self) -> bool {
+		let pieces: Vec<(PieceType, Color)> = self.squares.iter().flatten().filter_map(|square| match &square.piece {
+			Some(piece) if piece.piece_type != PieceType::King => Some((piece.piece_type, square.color)),
+			_ => None,
+		}).collect();
+		match pieces.as_slice() {
+			[] | [(PieceType::Bishop | PieceType::Knight, _)] => true,
+			[(_, first_color), ..] => pieces.iter().all(|(piece_type, color)| *piece_type == PieceType::Bishop && color == first_color),
+		}
+	}
+	// Whether any sequence of legal moves could still let this side mate, as FIDE asks when a flag falls
+	pub fn has_mating_material(&self, color: Color) -> bool {
+		let pieces = |of: Color| -> Vec<(PieceType, Color)> {
+			self.squares.iter().flatten().filter_map(|square| match &square.piece {
+				Some(piece) if piece.color == of && piece.piece_type != PieceType::King => Some((piece.piece_type, square.color)),
+				_ => None,
+			}).collect()
+		};
+		let (own, opponent) = (pieces(color), pieces(color.opponent()));
+		let bishops_on = |pieces: &[(PieceType, Color)], square_color: Color| {
+			pieces.iter().all(|(piece_type, color)| *piece_type == PieceType::Bishop && *color == square_color)
+		};
+		match own.as_slice() {
+			[] => false,
+			// A lone knight needs something of the opponent's to hem the king in
+			[(PieceType::Knight, _)] => !opponent.is_empty(),
+			// Bishops on one color can only mate with a blocker the bishops could never be, so not against bishops of the same color
+			[(_, first_color), ..] if bishops_on(&own, *first_color) => !bishops_on(&opponent, *first_color),
+			_ => true,
+		}
+	}
+	pub fn perft(&self, depth: u32) -> u64 {
+		if depth == 0 {
+			return 1;
+		}
+		let moves = self.legal_moves(self.side_to_move);
+		if depth == 1 {
+			return moves.len() as u64;
+		}
+		moves.iter().map(|chess_move| {
+			let mut next = self.clone();
+			next.apply_move(chess_move, self.side_to_move);
+			next.perft(depth - 1)
+		}).sum()
+	}
+	pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+		self.legal_moves(self.side_to_move).into_iter().map(|chess_move| {
+			let mut next = self.clone();
+			next.apply_move(&chess_move, self.side_to_move);
+			let nodes = if depth > 1 { next.perft(depth - 1) } else { 1 };
+			(chess_move, nodes)
+		}).collect()
+	}
+	fn candidate_moves(&self, color: Color) -> Vec<Move> {
+		let (home_rank, promote_rank, forward) = match color {
+			Color::White => (Rank::One, Rank::Eight, 1),
+			Color::Black => (Rank::Eight, Rank::One, -1),
+		};
+		let mut moves = Vec::<Move>::new();
+		let pieces = self.squares.iter().flatten().filter_map(|square| match &square.piece {
+			Some(piece) if piece.color == color => Some((square.coordinate, piece.piece_type)),
+			_ => None,
+		});
+		for (from, piece_type) in pieces {
+			let mut targets = Vec::<Coordinate>::new();
+			let open = |coord: Coordinate| !matches!(&self.get_square(coord).piece, Some(piece) if piece.color == color);
+			let slide = |targets: &mut Vec<Coordinate>, directions: &[(i8, i8)]| {
+				for &(rank_step, file_step) in directions {
+					let mut current = from;
+					while let Some(next) = current.offset(rank_step, file_step) {
+						if open(next) {
+							targets.push(next);
+						}
+						if self.get_square(next).piece.is_some() {
+							break;
+						}
+						current = next;
+					}
+				}
+			};
+			match piece_type {
+				PieceType::Pawn => {
+					if let Some(single) = from.offset(forward, 0) {
+						if self.get_square(single).piece.is_none() {
+							targets.push(single);
+							if let Some(double) = single.offset(forward, 0) {
+								if self.get_square(double).piece.is_none() {
+									targets.push(double);
+								}
+							}
+						}
+					}
+					for file_diff in [-1, 1] {
+						if let Some(capture) = from.offset(forward, file_diff) {
+							let enemy = matches!(&self.get_square(capture).piece, Some(piece) if piece.color != color);
+							if enemy || self.can_passant == Some(capture) {
+								targets.push(capture);
+							}
+						}
+					}
+				},
+				PieceType::Knight => {
+					targets.extend(KNIGHT_OFFSETS.iter().filter_map(|&(rank_diff, file_diff)| from.offset(rank_diff, file_diff)).filter(|&coord| open(coord)));
+				},
+				PieceType::King => {
+					targets.extend(KING_OFFSETS.iter().filter_map(|&(rank_diff, file_diff)| from.offset(rank_diff, file_diff)).filter(|&coord| open(coord)));
+					if from == (Coordinate { file: File::E, rank: home_rank }) {
+						moves.push(Move::new(piece_type, from, Coordinate { file: File::G, rank: home_rank }, MoveType::CastleKingSide, None));
+						moves.push(Move::new(piece_type, from, Coordinate { file: File::C, rank: home_rank }, MoveType::CastleQueenSide, None));
+					}
+				},
+				PieceType::Bishop => slide(&mut targets, &BISHOP_DIRECTIONS),
+				PieceType::Rook => slide(&mut targets, &ROOK_DIRECTIONS),
+				PieceType::Queen => {
+					slide(&mut targets, &ROOK_DIRECTIONS);
+					slide(&mut targets, &BISHOP_DIRECTIONS);
+				},
+			}
+			for to in targets {
+				if piece_type == PieceType::Pawn && to.rank == promote_rank {
+					for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+						moves.push(Move::new(piece_type, from, to, MoveType::Promotion, Some(promotion)));
+					}
+				} else {
+					moves.push(Move::new(piece_type, from, to, MoveType::Normal, None));
+				}
+			}
+		}
+		moves
+	}
+	fn path_clear(&self, from: Coordinate, to: Coordinate) -> bool {
+		let (rank_diff, file_diff) = to - from;
+		let (rank_step, file_step) = (rank_diff.signum(), file_diff.signum());
+		let distance = rank_diff.abs().max(file_diff.abs());
+		(1..distance).all(|step| {
+			self.get_square(from + (rank_step * step, file_step * step)).piece.is_none()
+		})
+	}
+	fn validate_move(&self, chess_move: &Move, color: Color) -> Result<(), MoveError> {
 		if chess_move.from == chess_move.to {
 			return Err(MoveError::Invalid)
 		}
 		let current_piece = &self.get_square(chess_move.from).piece;
 		if let Some(piece) = current_piece {
-			if piece.color() != player.color() || chess_move.piece_type != piece.piece_type {
+			if piece.color() != color || chess_move.piece_type != piece.piece_type {
 				return Err(MoveError::Invalid);
 			}
 		} else {
@@ -167,11 +461,11 @@ impl Board {
 		}
 		let occupying_piece = &self.get_square(chess_move.to).piece;
 		if let Some(piece) = occupying_piece {
-			if piece.color() == player.color() {
+			if piece.color() == color {
 				return Err(MoveError::Blocked);
 			}
 		}
-		let (home_rank, pawn_rank, promote_rank) = match player.color {
+		let (home_rank, pawn_rank, promote_rank) = match color {
 			Color::White => (Rank::One, Rank::Two, Rank::Eight),
 			Color::Black => (Rank::Eight, Rank::Seven, Rank::One),
 		};
@@ -179,20 +473,36 @@ impl Board {
 			chess_move.to.rank - chess_move.from.rank,
 			chess_move.to.file - chess_move.from.file
 		);
+		let opponent = color.opponent();
+		let king_home = Coordinate {
+			file: File::E,
+			rank: home_rank
+		};
+		let in_check = self.is_in_check(color);
 		match chess_move.move_type {
 			MoveType::CastleKingSide => {
-				match player.castling_rights {
+				match self.castling_rights(color) {
 					CastlingRights::Both | CastlingRights::King => {
-						let (f_clear, g_clear) = (self.get_square(Coordinate {
+						if chess_move.from != king_home || chess_move.to != (Coordinate { file: File::G, rank: home_rank }) {
+							return Err(MoveError::Invalid);
+						}
+						let rook = &self.get_square(Coordinate { file: File::H, rank: home_rank }).piece;
+						if !matches!(rook, Some(Piece { piece_type: PieceType::Rook, color: piece_color }) if *piece_color == color) {
+							return Err(MoveError::CastlingRights);
+						}
+						let (f_square, g_square) = (Coordinate {
 							file: File::F,
 							rank: home_rank
-						}).piece.is_none(), self.get_square(Coordinate {
+						}, Coordinate {
 							file: File::G,
 							rank: home_rank
-						}).piece.is_none());
-						if !f_clear || !g_clear {
+						});
+						if self.get_square(f_square).piece.is_some() || self.get_square(g_square).piece.is_some() {
 							return Err(MoveError::Blocked);
 						}
+						if in_check || self.is_attacked(f_square, opponent) || self.is_attacked(g_square, opponent) {
+							return Err(MoveError::InCheck);
+						}
 					},
 					_ => {
 						return Err(MoveError::CastlingRights)
@@ -200,138 +510,102 @@ impl Board {
 				}
 			},
 			MoveType::CastleQueenSide => {
-				match player.castling_rights {
+				match self.castling_rights(color) {
 					CastlingRights::Both | CastlingRights::Queen => {
-						let (b_clear, c_clear, d_clear) = (self.get_square(Coordinate {
+						if chess_move.from != king_home || chess_move.to != (Coordinate { file: File::C, rank: home_rank }) {
+							return Err(MoveError::Invalid);
+						}
+						let rook = &self.get_square(Coordinate { file: File::A, rank: home_rank }).piece;
+						if !matches!(rook, Some(Piece { piece_type: PieceType::Rook, color: piece_color }) if *piece_color == color) {
+							return Err(MoveError::CastlingRights);
+						}
+						let (b_square, c_square, d_square) = (Coordinate {
 							file: File::B,
 							rank: home_rank
-						}).piece.is_none(), self.get_square(Coordinate {
+						}, Coordinate {
 							file: File::C,
 							rank: home_rank
-						}).piece.is_none(), self.get_square(Coordinate {
+						}, Coordinate {
 							file: File::D,
 							rank: home_rank
-						}).piece.is_none());
-						if !b_clear || !c_clear || !d_clear {
+						});
+						if [b_square, c_square, d_square].iter().any(|&coord| self.get_square(coord).piece.is_some()) {
 							return Err(MoveError::Blocked);
 						}
+						if in_check || self.is_attacked(c_square, opponent) || self.is_attacked(d_square, opponent) {
+							return Err(MoveError::InCheck);
+						}
 					},
 					_ => {
 						return Err(MoveError::CastlingRights)
 					},
 				}
 			},
+			MoveType::Promotion if !matches!(chess_move.promotion, Some(PieceType::Knight | PieceType::Bishop | PieceType::Rook | PieceType::Queen)) => {
+				return Err(MoveError::Invalid);
+			},
 			_ => {}
 		}
 		match chess_move.piece_type {
 			PieceType::Pawn => {
-				let file_adjacent = (chess_move.to.file as usize).abs_diff(chess_move.from.file as usize) == 1;
-				let same_file = chess_move.to.file == chess_move.from.file;
-				let passantable = self.can_passant.is_some() && chess_move.to == self.can_passant.unwrap();
-				let forward_rank = chess_move.from.rank.forward(rank_diff.abs() as u8, player.color).expect("Error getting diff");
-				if forward_rank != chess_move.to.rank {
-					return Err(MoveError::Invalid);
-				}
-				let on_starting_rank = chess_move.from.rank == pawn_rank;
-				if !rank_diff == 1 && !(rank_diff.abs() == 2 && on_starting_rank) {
-					return Err(MoveError::Invalid);
-				}
-				if file_adjacent {
-					if occupying_piece.is_none() && !passantable {
-						return Err(MoveError::PawnNoCapture);
+				let forward: i8 = match color {
+					Color::White => 1,
+					Color::Black => -1,
+				};
+				let passantable = self.can_passant == Some(chess_move.to);
+				if file_diff == 0 {
+					if rank_diff == 2 * forward && chess_move.from.rank == pawn_rank {
+						if !self.path_clear(chess_move.from, chess_move.to) {
+							return Err(MoveError::Blocked);
+						}
+					} else if rank_diff != forward {
+						return Err(MoveError::Invalid);
 					}
-				} else {
 					if occupying_piece.is_some() {
 						return Err(MoveError::Blocked);
 					}
-				}
-				if !file_adjacent && !same_file {
+				} else if file_diff.abs() == 1 && rank_diff == forward {
+					if occupying_piece.is_none() && !passantable {
+						return Err(MoveError::PawnNoCapture);
+					}
+				} else {
 					return Err(MoveError::Invalid);
 				}
-				if chess_move.move_type == MoveType::Promotion && !(promote_rank == chess_move.to.rank) {
+				let promoting = chess_move.move_type == MoveType::Promotion;
+				if promoting && promote_rank != chess_move.to.rank {
 					return Err(MoveError::PromotionRank);
 				}
+				if !promoting && promote_rank == chess_move.to.rank {
+					return Err(MoveError::Invalid);
+				}
 			},
 			PieceType::Bishop => {
-				let direction: (bool, bool) = (file_diff > 0, rank_diff > 0);
 				if file_diff.abs() != rank_diff.abs() {
-					println!("{}{}", file_diff, rank_diff);
 					return Err(MoveError::Invalid);
 				}
-				for distance in 1..file_diff {
-					let file_distance = if direction.0 { distance } else { distance * -1 };
-					let rank_distance = if direction.1 { distance } else { distance * -1 };
-					let check_coord = chess_move.from + (file_distance, rank_distance);
-					let check_occupied = self.get_square(check_coord).piece.is_some();
-					if check_occupied {
-						return Err(MoveError::Blocked);
-					}
+				if !self.path_clear(chess_move.from, chess_move.to) {
+					return Err(MoveError::Blocked);
 				}
 			},
 			PieceType::Knight => {
-				let valid_diffs = [[2, 1], [1, 2], [-1, 2] ,[-2, 1], [-2, -1], [-1, -2], [1, -2], [2, -1]];
-				if !valid_diffs.contains(&[rank_diff, file_diff]) {
+				if !KNIGHT_OFFSETS.contains(&(rank_diff, file_diff)) {
 					return Err(MoveError::Invalid);
 				}
 			},
 			PieceType::Rook => {
-				let direction: (bool, bool) = (file_diff != 0, file_diff > 0 || rank_diff > 0 );
 				if file_diff * rank_diff != 0 {
 					return Err(MoveError::Invalid);
 				}
-				let check_coord = |distance| {
-					let file_distance = if direction.0 { if direction.1 { distance } else { distance * -1 }} else { 0 };
-					let rank_distance = if !direction.0 { if direction.1 { distance } else { distance * -1 }} else { 0 };
-					let coord = chess_move.from + (file_distance, rank_distance);
-					self.get_square(coord).piece.is_some()
-				};
-				for distance in 1..file_diff {
-					if check_coord(distance as i8) {
-						return Err(MoveError::Blocked);
-					}
-				}
-				for distance in 1..rank_diff {
-					if check_coord(distance as i8) {
-						return Err(MoveError::Blocked);
-					}
+				if !self.path_clear(chess_move.from, chess_move.to) {
+					return Err(MoveError::Blocked);
 				}
 			},
 			PieceType::Queen => {
-				if file_diff == 0 || rank_diff == 0 {
-					let direction: (bool, bool) = (file_diff != 0, file_diff > 0 || rank_diff > 0 );
-					if file_diff * rank_diff != 0 {
-						return Err(MoveError::Invalid);
-					}
-					let check_coord = |distance| {
-						let file_distance = if direction.0 { if direction.1 { distance } else { distance * -1 }} else { 0 };
-						let rank_distance = if !direction.0 { if direction.1 { distance } else { distance * -1 }} else { 0 };
-						let coord = chess_move.from + (file_distance, rank_distance);
-						self.get_square(coord).piece.is_some()
-					};
-					for distance in 1..file_diff {
-						if check_coord(distance) {
-							return Err(MoveError::Blocked);
-						}
-					}
-					for distance in 1..rank_diff {
-						if check_coord(distance) {
-							return Err(MoveError::Blocked);
-						}
-					}
-				} else {
-					let direction: (bool, bool) = (file_diff > 0, rank_diff > 0);
-					if file_diff.abs() != rank_diff.abs() {
-						return Err(MoveError::Invalid);
-					}
-					for distance in 1..file_diff {
-						let file_distance = if direction.0 { distance } else { distance * -1 };
-						let rank_distance = if direction.1 { distance } else { distance * -1 };
-						let check_coord = chess_move.from + (file_distance, rank_distance);
-						let check_occupied = self.get_square(check_coord).piece.is_some();
-						if check_occupied {
-							return Err(MoveError::Blocked);
-						}
-					}
+				if file_diff * rank_diff != 0 && file_diff.abs() != rank_diff.abs() {
+					return Err(MoveError::Invalid);
+				}
+				if !self.path_clear(chess_move.from, chess_move.to) {
+					return Err(MoveError::Blocked);
 				}
 			},
 			PieceType::King => {
@@ -340,7 +614,18 @@ impl Board {
 				}
 			},
 		};
-		return Ok(())
+		if chess_move.piece_type != PieceType::Pawn && chess_move.move_type == MoveType::Promotion {
+			return Err(MoveError::Invalid);
+		}
+		let mut after = self.clone();
+		after.apply_move(chess_move, color);
+		if after.is_in_check(color) {
+			if in_check || chess_move.piece_type == PieceType::King {
+				return Err(MoveError::InCheck);
+			}
+			return Err(MoveError::Pinned);
+		}
+		Ok(())
 	}
 }
 
@@ -352,8 +637,13 @@ impl Display for Board {
 
 impl Default for Board {
     fn default() -> Self {
-        Self {
+        let mut board = Self {
+			zobrist: 0,
 			can_passant: None,
+			side_to_move: Color::White,
+			castling_rights: (CastlingRights::Both, CastlingRights::Both),
+			halfmove_clock: 0,
+			fullmove_number: 1,
             squares: from_fn::<[Square;8], 8, _>(|rank| {
                 from_fn::<Square, 8, _>(|file| {
                     let index = rank * 8 + file;
@@ -386,7 +676,9 @@ impl Default for Board {
                     }
                 })
             })
-        }
+        };
+        board.rehash();
+        board
     }
 }
 
@@ -428,12 +720,21 @@ impl Display for Square {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Coordinate {
     pub file: File,
     pub rank: Rank
 }
 
+impl Coordinate {
+	pub fn offset(self, rank_diff: i8, file_diff: i8) -> Option<Coordinate> {
+		Some(Self {
+			rank: Rank::try_from(self.rank + rank_diff).ok()?,
+			file: File::try_from(self.file + file_diff).ok()?
+		})
+	}
+}
+
 impl Default for Coordinate {
 	fn default() -> Self {
 		Self { file: File::A, rank: Rank::One }
@@ -461,7 +762,7 @@ impl Add<(i8, i8)> for Coordinate {
 
 	fn add(self, rhs: (i8, i8)) -> Self::Output {
 		let rank = Rank::try_from(self.rank + rhs.0).expect("Error converting to rank!");
-		let file = File::try_from(self.rank + rhs.1).expect("Error converting to rank!");
+		let file = File::try_from(self.file + rhs.1).expect("Error converting to file!");
 		Self {
 			rank,
 			file
@@ -474,7 +775,7 @@ impl Sub<(i8, i8)> for Coordinate {
 
 	fn sub(self, rhs: (i8, i8)) -> Self::Output {
 		let rank = Rank::try_from(self.rank - rhs.0).expect("Error converting to rank!");
-		let file = File::try_from(self.rank - rhs.1).expect("Error converting to rank!");
+		let file = File::try_from(self.file - rhs.1).expect("Error converting to file!");
 		Self {
 			rank,
 			file
@@ -490,7 +791,6 @@ impl Display for Coordinate {
 
 impl From<(char, char)> for Coordinate {
 	fn from(value: (char, char)) -> Self {
-		println!("{}{}", value.0, value.1);
 		Self {
 			file: File::try_from(value.0).unwrap(),
 			rank: Rank::try_from(value.1).unwrap()
@@ -499,7 +799,7 @@ impl From<(char, char)> for Coordinate {
 }
 
 #[repr(usize)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum File {
     A,
     B,
@@ -515,7 +815,7 @@ impl TryFrom<u8> for File {
     type Error = &'static str;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value % 8{
+        match value {
             0 => Ok(File::A),
             1 => Ok(File::B),
             2 => Ok(File::C),
@@ -533,7 +833,7 @@ impl Add for File {
 	type Output = u8;
 
 	fn add(self, rhs: Self) -> Self::Output {
-		return self as u8 + rhs as u8;
+		self as u8 + rhs as u8
 	}
 
 }
@@ -542,7 +842,7 @@ impl Sub for File {
 	type Output = i8;
 
 	fn sub(self, rhs: Self) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs as i8
 	}
 }
 
@@ -550,7 +850,7 @@ impl Add<u8> for File {
 	type Output = u8;
 
 	fn add(self, rhs: u8) -> Self::Output {
-		return self as u8 + rhs;
+		self as u8 + rhs
 	}
 
 }
@@ -559,7 +859,7 @@ impl Sub<u8> for File {
 	type Output = i8;
 
 	fn sub(self, rhs: u8) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs as i8
 	}
 }
 
@@ -567,7 +867,7 @@ impl Add<i8> for File {
 	type Output = i8;
 
 	fn add(self, rhs: i8) -> Self::Output {
-		return self as i8 + rhs;
+		self as i8 + rhs
 	}
 
 }
@@ -576,7 +876,7 @@ impl Sub<i8> for File {
 	type Output = i8;
 
 	fn sub(self, rhs: i8) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs
 	}
 }
 
@@ -587,7 +887,7 @@ macro_rules! impl_try_from_file {
                 type Error = &'static str;
 
                 fn try_from(value: $t) -> Result<Self, Self::Error> {
-					File::try_from(value as u8)
+					u8::try_from(value).map_err(|_| "Value out of range for File (0–7)").and_then(File::try_from)
                 }
             }
         )*
@@ -614,9 +914,9 @@ impl TryFrom<char> for File {
     }
 }
 
-impl Into<char> for File {
-    fn into(self) -> char {
-        match self {
+impl From<File> for char {
+    fn from(value: File) -> Self {
+        match value {
             File::A => 'a',
             File::B => 'b',
             File::C => 'c',
@@ -630,7 +930,7 @@ impl Into<char> for File {
 }
 
 #[repr(usize)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Rank {
     One,
     Two,
@@ -651,9 +951,9 @@ impl Rank {
 	} 
 }
 
-impl Into<char> for Rank {
-    fn into(self) -> char {
-        match self {
+impl From<Rank> for char {
+    fn from(value: Rank) -> Self {
+        match value {
             Rank::One => '1',
             Rank::Two => '2',
             Rank::Three => '3',
@@ -670,7 +970,7 @@ impl Add for Rank {
 	type Output = u8;
 
 	fn add(self, rhs: Self) -> Self::Output {
-		return self as u8 + rhs as u8;
+		self as u8 + rhs as u8
 	}
 
 }
@@ -679,7 +979,7 @@ impl Sub for Rank {
 	type Output = i8;
 
 	fn sub(self, rhs: Self) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs as i8
 	}
 }
 
@@ -687,7 +987,7 @@ impl Add<u8> for Rank {
 	type Output = u8;
 
 	fn add(self, rhs: u8) -> Self::Output {
-		return self as u8 + rhs;
+		self as u8 + rhs
 	}
 
 }
@@ -696,7 +996,7 @@ impl Sub<u8> for Rank {
 	type Output = i8;
 
 	fn sub(self, rhs: u8) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs as i8
 	}
 }
 
@@ -704,7 +1004,7 @@ impl Add<i8> for Rank {
 	type Output = i8;
 
 	fn add(self, rhs: i8) -> Self::Output {
-		return self as i8 + rhs;
+		self as i8 + rhs
 	}
 
 }
@@ -713,7 +1013,7 @@ impl Sub<i8> for Rank {
 	type Output = i8;
 
 	fn sub(self, rhs: i8) -> Self::Output {
-		return self as i8 - rhs as i8;
+		self as i8 - rhs
 	}
 }
 
@@ -721,7 +1021,7 @@ impl TryFrom<u8> for Rank {
     type Error = &'static str;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value % 8 {
+        match value {
             0 => Ok(Rank::One),
             1 => Ok(Rank::Two),
             2 => Ok(Rank::Three),
@@ -742,7 +1042,7 @@ macro_rules! impl_try_from_rank {
                 type Error = &'static str;
 
                 fn try_from(value: $t) -> Result<Self, Self::Error> {
-					Rank::try_from(value as u8)
+					u8::try_from(value).map_err(|_| "Value out of range for Rank (0–7)").and_then(Rank::try_from)
                 }
             }
         )*
@@ -0,0 +1,245 @@
+use std::{fmt::{self, Display}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::{board::Color, game::Player};
+
+pub trait ClockSource: Send {
+	// Time since some fixed point, only differences between readings matter
+	fn now(&self) -> Duration;
+}
+
+pub struct SystemClock(Instant);
+
+impl SystemClock {
+	pub fn new() -> Self {
+		Self(Instant::now())
+	}
+}
+
+impl Default for SystemClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ClockSource for SystemClock {
+	fn now(&self) -> Duration {
+		self.0.elapsed()
+	}
+}
+
+// Only moves when told to, clones share the same time
+#[derive(Clone, Default)]
+pub struct ManualClock(Arc<Mutex<Duration>>);
+
+impl ManualClock {
+	pub fn advance(&self, by: Duration) {
+		*self.0.lock().unwrap() += by;
+	}
+}
+
+impl ClockSource for ManualClock {
+	fn now(&self) -> Duration {
+		*self.0.lock().unwrap()
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingMode {
+	// The bonus is added after every move
+	Fischer,
+	// Time used is given back after the move, up to the bonus
+	Bronstein,
+	// The clock only starts counting down once the bonus has passed
+	SimpleDelay,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControlStage {
+	// Moves to be made in this stage, None for the rest of the game
+	pub moves: Option<u32>,
+	pub base: Duration,
+	pub bonus: Duration,
+	pub mode: TimingMode,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControl {
+	pub stages: Vec<TimeControlStage>,
+}
+
+impl TimeControl {
+	pub fn sudden_death(base: Duration) -> Self {
+		Self {
+			stages: vec![TimeControlStage { moves: None, base, bonus: Duration::ZERO, mode: TimingMode::Fischer }],
+		}
+	}
+	// The stage a player is in after making this many moves; a last stage with a move count repeats
+	pub fn stage(&self, moves: u32) -> &TimeControlStage {
+		let mut remaining = moves;
+		for stage in &self.stages {
+			match stage.moves {
+				Some(count) if remaining >= count => remaining -= count,
+				_ => return stage,
+			}
+		}
+		self.stages.last().expect("time control has at least one stage")
+	}
+	// The stage whose base time is added once a player has made this many moves
+	fn stage_reached(&self, moves: u32) -> Option<&TimeControlStage> {
+		let mut total = 0;
+		for (index, stage) in self.stages.iter().enumerate() {
+			total += stage.moves?;
+			if moves < total {
+				return None;
+			}
+			if moves == total {
+				return Some(self.stages.get(index + 1).unwrap_or(stage));
+			}
+		}
+		let last = self.stages.last()?;
+		(moves - total).is_multiple_of(last.moves?).then_some(last)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControlError(pub String);
+
+impl Display for TimeControlError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Invalid time control stage '{}'!", self.0)
+	}
+}
+
+// PGN TimeControl syntax in seconds, such as "40/5400+30:1800+30", with 'd' for a simple delay and 'b' for Bronstein
+impl TryFrom<&str> for TimeControl {
+	type Error = TimeControlError;
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		let stages = value.split(':').map(|field| {
+			let error = || TimeControlError(field.to_string());
+			let (moves, rest) = match field.split_once('/') {
+				Some((moves, rest)) => (Some(moves.parse().ok().filter(|moves| *moves > 0).ok_or_else(error)?), rest),
+				None => (None, field),
+			};
+			let (base, bonus, mode) = match rest.find(['+', 'd', 'b']) {
+				Some(index) => {
+					let mode = match &rest[index..index + 1] {
+						"+" => TimingMode::Fischer,
+						"d" => TimingMode::SimpleDelay,
+						_ => TimingMode::Bronstein,
+					};
+					(&rest[..index], rest[index + 1..].parse().map_err(|_| error())?, mode)
+				},
+				None => (rest, 0, TimingMode::Fischer),
+			};
+			Ok(TimeControlStage {
+				moves,
+				base: Duration::from_secs(base.parse().map_err(|_| error())?),
+				bonus: Duration::from_secs(bonus),
+				mode,
+			})
+		}).collect::<Result<Vec<_>, _>>()?;
+		Ok(Self { stages })
+	}
+}
+
+impl Display for TimeControl {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let stages: Vec<String> = self.stages.iter().map(|stage| {
+			let moves = stage.moves.map_or(String::new(), |moves| format!("{}/", moves));
+			let bonus = match (stage.bonus.is_zero(), stage.mode) {
+				(true, _) => String::new(),
+				(false, TimingMode::Fischer) => format!("+{}", stage.bonus.as_secs()),
+				(false, TimingMode::SimpleDelay) => format!("d{}", stage.bonus.as_secs()),
+				(false, TimingMode::Bronstein) => format!("b{}", stage.bonus.as_secs()),
+			};
+			format!("{}{}{}", moves, stage.base.as_secs(), bonus)
+		}).collect();
+		write!(f, "{}", stages.join(":"))
+	}
+}
+
+pub struct Clock {
+	pub control: TimeControl,
+	source: Box<dyn ClockSource>,
+	turn_start: Duration,
+	// Moves made by White and Black
+	moves: (u32, u32),
+}
+
+impl Clock {
+	pub fn new<S: ClockSource + 'static>(control: TimeControl, source: S) -> Self {
+		Self {
+			control,
+			turn_start: source.now(),
+			source: Box::new(source),
+			moves: (0, 0),
+		}
+	}
+	pub fn restart_turn(&mut self) {
+		self.turn_start = self.source.now();
+	}
+	pub fn elapsed(&self) -> Duration {
+		self.source.now().saturating_sub(self.turn_start)
+	}
+	fn moves(&self, color: Color) -> u32 {
+		match color {
+			Color::White => self.moves.0,
+			Color::Black => self.moves.1,
+		}
+	}
+	// What the player to move has left right now, counting an unused simple delay as spare time
+	pub fn remaining(&self, player: &Player) -> Duration {
+		let stage = self.control.stage(self.moves(player.color));
+		let charged = match stage.mode {
+			TimingMode::SimpleDelay => self.elapsed().saturating_sub(stage.bonus),
+			TimingMode::Fischer | TimingMode::Bronstein => self.elapsed(),
+		};
+		player.time.saturating_sub(charged)
+	}
+	pub fn is_flagged(&self, player: &Player) -> bool {
+		self.remaining(player).is_zero()
+	}
+	// Charges the move just made to the player and starts the opponent's turn
+	pub fn complete_move(&mut self, player: &mut Player) {
+		let elapsed = self.elapsed();
+		let stage = *self.control.stage(self.moves(player.color));
+		let moves = self.moves(player.color) + 1;
+		match player.color {
+			Color::White => self.moves.0 = moves,
+			Color::Black => self.moves.1 = moves,
+		}
+		match stage.mode {
+			TimingMode::Fischer => {
+				player.decrement_time(elapsed);
+				player.increment_time(stage.bonus);
+			},
+			TimingMode::Bronstein => {
+				player.decrement_time(elapsed);
+				player.increment_time(elapsed.min(stage.bonus));
+			},
+			TimingMode::SimpleDelay => player.decrement_time(elapsed.saturating_sub(stage.bonus)),
+		}
+		if let Some(next) = self.control.stage_reached(moves) {
+			player.increment_time(next.base);
+		}
+		self.restart_turn();
+	}
+	// Forgets the player's last move, whose time is restored by the game, and restarts their turn
+	pub fn take_back_move(&mut self, color: Color) {
+		match color {
+			Color::White => self.moves.0 = self.moves.0.saturating_sub(1),
+			Color::Black => self.moves.1 = self.moves.1.saturating_sub(1),
+		}
+		self.restart_turn();
+	}
+}
+
+pub fn format_duration(duration: Duration) -> String {
+	let seconds = duration.as_secs();
+	if seconds >= 3600 {
+		format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+	} else {
+		format!("{}:{:02}", seconds / 60, seconds % 60)
+	}
+}
@@ -0,0 +1,197 @@
+use std::{cell::RefCell, io::{BufRead, Write}, rc::Rc, sync::atomic::AtomicBool, time::Duration};
+
+use crate::{board::Color, clock, eval::EvalWeights, game::{Game, Move, MoveError, Takeback}, search::{self, SearchLimits}, tt::{TranspositionTable, DEFAULT_HASH_MB}};
+
+pub enum Action {
+	Move(Move),
+	ClaimDraw,
+	// Asks the opponent to let moves be taken back
+	Takeback(Takeback),
+	Resign,
+	// The controller has nothing more to play, such as a script that ran out of moves
+	Abandon,
+}
+
+pub trait PlayerController {
+	fn choose(&mut self, game: &Game) -> Action;
+	// Called when the chosen move could not be played, before being asked again
+	fn rejected(&mut self, _game: &Game, _error: MoveError) {}
+	// Called when the opponent asks to take moves back, which is declined unless overridden
+	fn takeback_offered(&mut self, _game: &Game, _takeback: Takeback) -> bool {
+		false
+	}
+}
+
+// Accepts SAN or UCI notation for a move, as typed by a person or kept in a script
+pub fn parse_move(input: &str, game: &Game) -> Result<Move, MoveError> {
+	Move::try_from((input, &game.board)).or_else(|err| Move::from_uci(input, &game.board).map_err(|_| err))
+}
+
+pub struct Terminal<R, W> {
+	input: R,
+	output: W,
+}
+
+impl<R: BufRead, W: Write> Terminal<R, W> {
+	pub fn new(input: R, output: W) -> Self {
+		Self { input, output }
+	}
+	// The next non-empty line, or None once the input is closed
+	pub fn read_line(&mut self) -> Option<String> {
+		let mut line = String::new();
+		loop {
+			line.clear();
+			match self.input.read_line(&mut line) {
+				Ok(0) | Err(_) => return None,
+				Ok(_) if line.trim().is_empty() => continue,
+				Ok(_) => return Some(line.trim().to_string()),
+			}
+		}
+	}
+	pub fn write(&mut self, text: &str) {
+		let _ = write!(self.output, "{}", text);
+		let _ = self.output.flush();
+	}
+	pub fn write_line(&mut self, line: &str) {
+		self.write(&format!("{}\n", line));
+	}
+}
+
+// Both players at one terminal share it, so it is held behind a RefCell
+pub struct TerminalHuman<R, W>(Rc<RefCell<Terminal<R, W>>>);
+
+impl<R: BufRead, W: Write> TerminalHuman<R, W> {
+	pub fn new(terminal: Rc<RefCell<Terminal<R, W>>>) -> Self {
+		Self(terminal)
+	}
+}
+
+impl<R: BufRead, W: Write> PlayerController for TerminalHuman<R, W> {
+	fn choose(&mut self, game: &Game) -> Action {
+		let mut terminal = self.0.borrow_mut();
+		loop {
+			terminal.write_line(&game.turns_to_string());
+			let color = game.board.side_to_move;
+			terminal.write_line(&game.board.to_string(color.into()));
+			if let Some(clock) = &game.clock {
+				terminal.write_line(&format!("White {} | Black {}", clock::format_duration(clock.remaining(&game.players.0)), clock::format_duration(clock.remaining(&game.players.1))));
+			}
+			terminal.write(&format!("Enter move for {}: ", color.to_string().to_lowercase()));
+			// A closed input means the player has left
+			let Some(input) = terminal.read_line() else { return Action::Abandon };
+			match input.as_str() {
+				"draw" if game.claimable_draw().is_some() => return Action::ClaimDraw,
+				"draw" => terminal.write_line("No draw can be claimed!"),
+				"resign" => return Action::Resign,
+				"undo" => return Action::Takeback(Takeback::Ply),
+				"takeback" => return Action::Takeback(Takeback::Turn),
+				_ => match parse_move(&input, game) {
+					Ok(chess_move) => return Action::Move(chess_move),
+					Err(err) => terminal.write_line(err.as_str()),
+				},
+			}
+		}
+	}
+	fn rejected(&mut self, _game: &Game, error: MoveError) {
+		self.0.borrow_mut().write_line(error.as_str());
+	}
+	fn takeback_offered(&mut self, game: &Game, takeback: Takeback) -> bool {
+		let mut terminal = self.0.borrow_mut();
+		terminal.write(&format!("{} asks to take back {}, accept? (y/n): ", game.board.side_to_move, takeback.as_str()));
+		terminal.read_line().is_some_and(|answer| answer.to_lowercase().starts_with('y'))
+	}
+}
+
+pub struct ScriptedController {
+	moves: Vec<String>,
+	next: usize,
+}
+
+impl ScriptedController {
+	pub fn new(moves: &[&str]) -> Self {
+		Self {
+			moves: moves.iter().map(|chess_move| chess_move.to_string()).collect(),
+			next: 0,
+		}
+	}
+}
+
+impl PlayerController for ScriptedController {
+	fn choose(&mut self, game: &Game) -> Action {
+		let Some(input) = self.moves.get(self.next) else { return Action::Abandon };
+		self.next += 1;
+		match parse_move(input, game) {
+			Ok(chess_move) => Action::Move(chess_move),
+			Err(_) => Action::Abandon,
+		}
+	}
+}
+
+// Plays uniformly random legal moves from a seeded xorshift generator, so games can be replayed
+pub struct RandomMover(u64);
+
+impl RandomMover {
+	pub fn new(seed: u64) -> Self {
+		// Xorshift never leaves zero
+		Self(seed.max(1))
+	}
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+}
+
+impl PlayerController for RandomMover {
+	fn choose(&mut self, game: &Game) -> Action {
+		let mut moves = game.board.legal_moves(game.board.side_to_move);
+		if moves.is_empty() {
+			return Action::Abandon;
+		}
+		let index = (self.next() % moves.len() as u64) as usize;
+		Action::Move(moves.swap_remove(index))
+	}
+}
+
+pub struct EngineController {
+	pub limits: SearchLimits,
+	pub weights: EvalWeights,
+	tt: TranspositionTable,
+}
+
+impl EngineController {
+	pub fn new(limits: SearchLimits) -> Self {
+		Self {
+			limits,
+			weights: EvalWeights::default(),
+			tt: TranspositionTable::new(DEFAULT_HASH_MB),
+		}
+	}
+}
+
+impl PlayerController for EngineController {
+	fn choose(&mut self, game: &Game) -> Action {
+		let mut limits = self.limits;
+		// Without a fixed limit the engine budgets from its own clock
+		if let (None, None, Some(clock)) = (limits.depth, limits.time, &game.clock) {
+			let player = match game.board.side_to_move {
+				Color::White => &game.players.0,
+				Color::Black => &game.players.1,
+			};
+			limits.time = Some(clock.remaining(player) / 30);
+		}
+		if limits == SearchLimits::default() {
+			limits.time = Some(Duration::from_secs(1));
+		}
+		let result = search::search(&game.board, limits, &self.weights, &mut self.tt, &AtomicBool::new(false), |_| {});
+		// A draw is only worth claiming when the search sees nothing better
+		if game.claimable_draw().is_some() && result.score <= 0 {
+			return Action::ClaimDraw;
+		}
+		match result.best_move() {
+			Some(chess_move) => Action::Move(chess_move.clone()),
+			None => Action::Abandon,
+		}
+	}
+}
@@ -0,0 +1,443 @@
+use std::{fmt::{self, Display}, ops::{Add, AddAssign, Mul, Neg, Sub}, path::Path};
+
+use crate::{board::{Board, Color, Coordinate, File, BISHOP_DIRECTIONS, KING_OFFSETS, KNIGHT_OFFSETS, ROOK_DIRECTIONS}, pieces::PieceType};
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "bishop", "knight", "rook", "queen", "king"];
+// Game phase contributed by each piece type, 24 being the full starting complement
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// Middlegame and endgame values, blended by the game phase
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score(pub i32, pub i32);
+
+impl Score {
+	pub fn taper(self, phase: i32) -> i32 {
+		(self.0 * phase + self.1 * (MAX_PHASE - phase)) / MAX_PHASE
+	}
+}
+
+impl Add for Score {
+	type Output = Self;
+
+	fn add(self, rhs: Self) -> Self::Output {
+		Score(self.0 + rhs.0, self.1 + rhs.1)
+	}
+}
+
+impl AddAssign for Score {
+	fn add_assign(&mut self, rhs: Self) {
+		*self = *self + rhs;
+	}
+}
+
+impl Sub for Score {
+	type Output = Self;
+
+	fn sub(self, rhs: Self) -> Self::Output {
+		Score(self.0 - rhs.0, self.1 - rhs.1)
+	}
+}
+
+impl Neg for Score {
+	type Output = Self;
+
+	fn neg(self) -> Self::Output {
+		Score(-self.0, -self.1)
+	}
+}
+
+impl Mul<i32> for Score {
+	type Output = Self;
+
+	fn mul(self, rhs: i32) -> Self::Output {
+		Score(self.0 * rhs, self.1 * rhs)
+	}
+}
+
+impl Display for Score {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}/{}", self.0, self.1)
+	}
+}
+
+// Tables are laid out as seen from White's side of a diagram, a8 first and h1 last
+const fn table(middlegame: [i32; 64], endgame: [i32; 64]) -> [Score; 64] {
+	let mut table = [Score(0, 0); 64];
+	let mut index = 0;
+	while index < 64 {
+		table[index] = Score(middlegame[index], endgame[index]);
+		index += 1;
+	}
+	table
+}
+
+const PAWN_TABLE: [i32; 64] = [
+	  0,   0,   0,   0,   0,   0,   0,   0,
+	 50,  50,  50,  50,  50,  50,  50,  50,
+	 10,  10,  20,  30,  30,  20,  10,  10,
+	  5,   5,  10,  25,  25,  10,   5,   5,
+	  0,   0,   0,  20,  20,   0,   0,   0,
+	  5,  -5, -10,   0,   0, -10,  -5,   5,
+	  5,  10,  10, -20, -20,  10,  10,   5,
+	  0,   0,   0,   0,   0,   0,   0,   0,
+];
+const PAWN_ENDGAME_TABLE: [i32; 64] = [
+	  0,   0,   0,   0,   0,   0,   0,   0,
+	 80,  80,  80,  80,  80,  80,  80,  80,
+	 50,  50,  50,  50,  50,  50,  50,  50,
+	 30,  30,  30,  30,  30,  30,  30,  30,
+	 15,  15,  15,  15,  15,  15,  15,  15,
+	  5,   5,   5,   5,   5,   5,   5,   5,
+	  0,   0,   0,   0,   0,   0,   0,   0,
+	  0,   0,   0,   0,   0,   0,   0,   0,
+];
+const KNIGHT_TABLE: [i32; 64] = [
+	-50, -40, -30, -30, -30, -30, -40, -50,
+	-40, -20,   0,   0,   0,   0, -20, -40,
+	-30,   0,  10,  15,  15,  10,   0, -30,
+	-30,   5,  15,  20,  20,  15,   5, -30,
+	-30,   0,  15,  20,  20,  15,   0, -30,
+	-30,   5,  10,  15,  15,  10,   5, -30,
+	-40, -20,   0,   5,   5,   0, -20, -40,
+	-50, -40, -30, -30, -30, -30, -40, -50,
+];
+const BISHOP_TABLE: [i32; 64] = [
+	-20, -10, -10, -10, -10, -10, -10, -20,
+	-10,   0,   0,   0,   0,   0,   0, -10,
+	-10,   0,   5,  10,  10,   5,   0, -10,
+	-10,   5,   5,  10,  10,   5,   5, -10,
+	-10,   0,  10,  10,  10,  10,   0, -10,
+	-10,  10,  10,  10,  10,  10,  10, -10,
+	-10,   5,   0,   0,   0,   0,   5, -10,
+	-20, -10, -10, -10, -10, -10, -10, -20,
+];
+const ROOK_TABLE: [i32; 64] = [
+	  0,   0,   0,   0,   0,   0,   0,   0,
+	  5,  10,  10,  10,  10,  10,  10,   5,
+	 -5,   0,   0,   0,   0,   0,   0,  -5,
+	 -5,   0,   0,   0,   0,   0,   0,  -5,
+	 -5,   0,   0,   0,   0,   0,   0,  -5,
+	 -5,   0,   0,   0,   0,   0,   0,  -5,
+	 -5,   0,   0,   0,   0,   0,   0,  -5,
+	  0,   0,   0,   5,   5,   0,   0,   0,
+];
+const QUEEN_TABLE: [i32; 64] = [
+	-20, -10, -10,  -5,  -5, -10, -10, -20,
+	-10,   0,   0,   0,   0,   0,   0, -10,
+	-10,   0,   5,   5,   5,   5,   0, -10,
+	 -5,   0,   5,   5,   5,   5,   0,  -5,
+	  0,   0,   5,   5,   5,   5,   0,  -5,
+	-10,   5,   5,   5,   5,   5,   0, -10,
+	-10,   0,   5,   0,   0,   0,   0, -10,
+	-20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+const KING_TABLE: [i32; 64] = [
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-30, -40, -40, -50, -50, -40, -40, -30,
+	-20, -30, -30, -40, -40, -30, -30, -20,
+	-10, -20, -20, -20, -20, -20, -20, -10,
+	 20,  20,   0,   0,   0,   0,  20,  20,
+	 20,  30,  10,   0,   0,  10,  30,  20,
+];
+const KING_ENDGAME_TABLE: [i32; 64] = [
+	-50, -40, -30, -20, -20, -30, -40, -50,
+	-30, -20, -10,   0,   0, -10, -20, -30,
+	-30, -10,  20,  30,  30,  20, -10, -30,
+	-30, -10,  30,  40,  40,  30, -10, -30,
+	-30, -10,  30,  40,  40,  30, -10, -30,
+	-30, -10,  20,  30,  30,  20, -10, -30,
+	-30, -30,   0,   0,   0,   0, -30, -30,
+	-50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalWeights {
+	pub material: [Score; 6],
+	pub piece_square: [[Score; 64]; 6],
+	pub doubled_pawn: Score,
+	pub isolated_pawn: Score,
+	// Indexed by the rank the pawn stands on, counted from its own side
+	pub passed_pawn: [Score; 8],
+	pub pawn_shield: Score,
+	pub king_open_file: Score,
+	pub king_zone_attack: Score,
+	// Per square reachable by each piece type
+	pub mobility: [Score; 6],
+}
+
+impl EvalWeights {
+	pub const DEFAULT: EvalWeights = EvalWeights {
+		material: [Score(100, 120), Score(330, 320), Score(320, 300), Score(500, 520), Score(900, 940), Score(0, 0)],
+		piece_square: [
+			table(PAWN_TABLE, PAWN_ENDGAME_TABLE),
+			table(BISHOP_TABLE, BISHOP_TABLE),
+			table(KNIGHT_TABLE, KNIGHT_TABLE),
+			table(ROOK_TABLE, ROOK_TABLE),
+			table(QUEEN_TABLE, QUEEN_TABLE),
+			table(KING_TABLE, KING_ENDGAME_TABLE),
+		],
+		doubled_pawn: Score(-10, -20),
+		isolated_pawn: Score(-15, -10),
+		passed_pawn: [Score(0, 0), Score(5, 10), Score(10, 15), Score(15, 25), Score(25, 45), Score(40, 75), Score(60, 110), Score(0, 0)],
+		pawn_shield: Score(12, 0),
+		king_open_file: Score(-20, 0),
+		king_zone_attack: Score(-10, -2),
+		mobility: [Score(0, 0), Score(4, 5), Score(4, 4), Score(2, 4), Score(1, 2), Score(0, 0)],
+	};
+
+	// Only the terms named in the config are changed, the rest keep their defaults
+	pub fn from_config(config: &str) -> Result<Self, EvalConfigError> {
+		let mut weights = Self::default();
+		// Indented lines continue the values of the term above, which lets tables span several lines
+		let mut entries: Vec<(usize, &str, String)> = Vec::new();
+		for (index, raw_line) in config.lines().enumerate() {
+			let line = raw_line.split('#').next().unwrap_or_default();
+			if line.trim().is_empty() {
+				continue;
+			}
+			match (line.split_once('='), entries.last_mut()) {
+				(None, Some((_, _, values))) if line.starts_with(char::is_whitespace) => {
+					values.push(' ');
+					values.push_str(line.trim());
+				},
+				(Some((name, values)), _) => entries.push((index + 1, name.trim(), values.trim().to_string())),
+				(None, _) => return Err(EvalConfigError::Syntax(index + 1)),
+			}
+		}
+		for (line_number, name, values) in entries {
+			let values = values.split_whitespace().map(|value| {
+				let (middlegame, endgame) = value.split_once('/').unwrap_or((value, value));
+				Some(Score(middlegame.parse().ok()?, endgame.parse().ok()?))
+			}).collect::<Option<Vec<Score>>>().ok_or_else(|| EvalConfigError::Value(line_number, name.to_string()))?;
+			let term = weights.terms_mut().into_iter().find(|(term, _)| term == name)
+				.ok_or_else(|| EvalConfigError::UnknownTerm(line_number, name.to_string()))?.1;
+			if term.len() != values.len() {
+				return Err(EvalConfigError::Count(line_number, term.len(), values.len()));
+			}
+			term.copy_from_slice(&values);
+		}
+		Ok(weights)
+	}
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EvalConfigError> {
+		let config = std::fs::read_to_string(path).map_err(|err| EvalConfigError::Io(err.to_string()))?;
+		Self::from_config(&config)
+	}
+	pub fn to_config(&self) -> String {
+		let mut config = String::new();
+		for (name, values) in self.terms() {
+			let values: Vec<String> = values.iter().map(Score::to_string).collect();
+			// Tables are written one rank to a line so they read like a diagram
+			if values.len() == 64 {
+				config += &format!("{} =\n", name);
+				for rank in values.chunks(8) {
+					config += &format!("\t{}\n", rank.join(" "));
+				}
+			} else {
+				config += &format!("{} = {}\n", name, values.join(" "));
+			}
+		}
+		config
+	}
+	fn term_names() -> Vec<String> {
+		let mut names: Vec<String> = PIECE_NAMES.iter().map(|name| format!("material.{}", name)).collect();
+		names.extend(PIECE_NAMES.iter().map(|name| format!("pst.{}", name)));
+		names.extend(["doubled_pawn", "isolated_pawn", "passed_pawn", "pawn_shield", "king_open_file", "king_zone_attack"].map(String::from));
+		names.extend(PIECE_NAMES.iter().map(|name| format!("mobility.{}", name)));
+		names
+	}
+	fn terms(&self) -> Vec<(String, &[Score])> {
+		let mut values: Vec<&[Score]> = self.material.chunks(1).collect();
+		values.extend(self.piece_square.iter().map(|table| table.as_slice()));
+		values.extend([&self.doubled_pawn, &self.isolated_pawn].map(std::slice::from_ref));
+		values.push(&self.passed_pawn);
+		values.extend([&self.pawn_shield, &self.king_open_file, &self.king_zone_attack].map(std::slice::from_ref));
+		values.extend(self.mobility.chunks(1));
+		Self::term_names().into_iter().zip(values).collect()
+	}
+	fn terms_mut(&mut self) -> Vec<(String, &mut [Score])> {
+		let mut values: Vec<&mut [Score]> = self.material.chunks_mut(1).collect();
+		values.extend(self.piece_square.iter_mut().map(|table| table.as_mut_slice()));
+		values.extend([&mut self.doubled_pawn, &mut self.isolated_pawn].map(std::slice::from_mut));
+		values.push(&mut self.passed_pawn);
+		values.extend([&mut self.pawn_shield, &mut self.king_open_file, &mut self.king_zone_attack].map(std::slice::from_mut));
+		values.extend(self.mobility.chunks_mut(1));
+		Self::term_names().into_iter().zip(values).collect()
+	}
+}
+
+impl Default for EvalWeights {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalConfigError {
+	Io(String),
+	Syntax(usize),
+	UnknownTerm(usize, String),
+	Value(usize, String),
+	Count(usize, usize, usize),
+}
+
+impl Display for EvalConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			EvalConfigError::Io(err) => write!(f, "{}", err),
+			EvalConfigError::Syntax(line) => write!(f, "Line {}: expected 'term = values'!", line),
+			EvalConfigError::UnknownTerm(line, name) => write!(f, "Line {}: unknown term '{}'!", line, name),
+			EvalConfigError::Value(line, name) => write!(f, "Line {}: invalid value for '{}'!", line, name),
+			EvalConfigError::Count(line, expected, found) => write!(f, "Line {}: expected {} values but found {}!", line, expected, found),
+		}
+	}
+}
+
+pub struct EvalTrace {
+	// Each term as (name, White, Black) before tapering
+	pub terms: Vec<(&'static str, Score, Score)>,
+	pub phase: i32,
+	// Tapered total from White's point of view
+	pub total: i32,
+}
+
+impl Display for EvalTrace {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{:<16}{:>12}{:>12}{:>12}", "Term", "White", "Black", "Total")?;
+		for (name, white, black) in &self.terms {
+			writeln!(f, "{:<16}{:>12}{:>12}{:>12}", name, white.to_string(), black.to_string(), (*white - *black).taper(self.phase))?;
+		}
+		write!(f, "Phase {}/{}, total {} from White's side", self.phase, MAX_PHASE, self.total)
+	}
+}
+
+// Score from the side to move's point of view, as the search expects
+pub fn evaluate(board: &Board, weights: &EvalWeights) -> i32 {
+	let total = trace(board, weights).total;
+	match board.side_to_move {
+		Color::White => total,
+		Color::Black => -total,
+	}
+}
+
+pub fn trace(board: &Board, weights: &EvalWeights) -> EvalTrace {
+	let white = ColorTerms::new(board, weights, Color::White);
+	let black = ColorTerms::new(board, weights, Color::Black);
+	let phase = (white.phase + black.phase).min(MAX_PHASE);
+	let terms = vec![
+		("Material", white.material, black.material),
+		("Piece-square", white.piece_square, black.piece_square),
+		("Pawn structure", white.pawns, black.pawns),
+		("King safety", white.king_safety, black.king_safety),
+		("Mobility", white.mobility, black.mobility),
+	];
+	let total = terms.iter().map(|(_, white, black)| *white - *black).fold(Score::default(), Add::add).taper(phase);
+	EvalTrace { terms, phase, total }
+}
+
+#[derive(Default)]
+struct ColorTerms {
+	material: Score,
+	piece_square: Score,
+	pawns: Score,
+	king_safety: Score,
+	mobility: Score,
+	phase: i32,
+}
+
+impl ColorTerms {
+	fn new(board: &Board, weights: &EvalWeights, color: Color) -> Self {
+		let mut terms = ColorTerms::default();
+		let forward: i8 = match color {
+			Color::White => 1,
+			Color::Black => -1,
+		};
+		// Rank counted from this side, 0 being its back rank
+		let relative_rank = |coordinate: Coordinate| match color {
+			Color::White => coordinate.rank as usize,
+			Color::Black => 7 - coordinate.rank as usize,
+		};
+		let pawns_on = |color: Color, file: usize| -> Vec<Coordinate> {
+			(0..8).map(|rank| &board.squares[rank][file])
+				.filter(|square| matches!(&square.piece, Some(piece) if piece.color == color && piece.piece_type == PieceType::Pawn))
+				.map(|square| square.coordinate)
+				.collect()
+		};
+		for square in board.squares.iter().flatten() {
+			let Some(piece) = &square.piece else { continue };
+			if piece.color != color {
+				continue;
+			}
+			let index = piece.piece_type as usize;
+			let coordinate = square.coordinate;
+			terms.material += weights.material[index];
+			terms.piece_square += weights.piece_square[index][(7 - relative_rank(coordinate)) * 8 + coordinate.file as usize];
+			terms.phase += PHASE_WEIGHTS[index];
+			let reachable = |directions: &[(i8, i8)], slides: bool| {
+				directions.iter().map(|&(rank_step, file_step)| {
+					let mut count = 0;
+					let mut current = coordinate;
+					while let Some(next) = current.offset(rank_step, file_step) {
+						match &board.get_square(next).piece {
+							Some(other) => {
+								count += (other.color != color) as i32;
+								break;
+							},
+							None => count += 1,
+						}
+						if !slides {
+							break;
+						}
+						current = next;
+					}
+					count
+				}).sum::<i32>()
+			};
+			let mobility = match piece.piece_type {
+				PieceType::Knight => reachable(&KNIGHT_OFFSETS, false),
+				PieceType::Bishop => reachable(&BISHOP_DIRECTIONS, true),
+				PieceType::Rook => reachable(&ROOK_DIRECTIONS, true),
+				PieceType::Queen => reachable(&ROOK_DIRECTIONS, true) + reachable(&BISHOP_DIRECTIONS, true),
+				PieceType::Pawn | PieceType::King => 0,
+			};
+			terms.mobility += weights.mobility[index] * mobility;
+		}
+		for file in 0..8 {
+			let own = pawns_on(color, file);
+			if own.len() > 1 {
+				terms.pawns += weights.doubled_pawn * (own.len() as i32 - 1);
+			}
+			let neighbours = [file.wrapping_sub(1), file + 1].into_iter().filter(|file| *file < 8);
+			if !own.is_empty() && neighbours.clone().all(|file| pawns_on(color, file).is_empty()) {
+				terms.pawns += weights.isolated_pawn * own.len() as i32;
+			}
+			for pawn in own {
+				let blockers = [file.wrapping_sub(1), file, file + 1].into_iter().filter(|file| *file < 8)
+					.flat_map(|file| pawns_on(color.opponent(), file))
+					.any(|enemy| relative_rank(enemy) > relative_rank(pawn));
+				if !blockers {
+					terms.pawns += weights.passed_pawn[relative_rank(pawn)];
+				}
+			}
+		}
+		if let Some(king) = board.king_coordinate(color) {
+			for file_diff in -1..=1 {
+				let Ok(file) = File::try_from(king.file + file_diff) else { continue };
+				let shield = (1..=2).filter_map(|distance| king.offset(forward * distance, file_diff))
+					.filter(|coordinate| matches!(&board.get_square(*coordinate).piece, Some(piece) if piece.color == color && piece.piece_type == PieceType::Pawn))
+					.count();
+				terms.king_safety += weights.pawn_shield * shield as i32;
+				if pawns_on(color, file as usize).is_empty() {
+					terms.king_safety += weights.king_open_file;
+				}
+			}
+			let attacked = KING_OFFSETS.iter().filter_map(|&(rank_diff, file_diff)| king.offset(rank_diff, file_diff))
+				.filter(|coordinate| board.is_attacked(*coordinate, color.opponent()))
+				.count();
+			terms.king_safety += weights.king_zone_attack * attacked as i32;
+		}
+		terms
+	}
+}
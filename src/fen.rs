@@ -0,0 +1,172 @@
+use std::fmt::{self, Display};
+
+use crate::{board::{Board, Color, Coordinate, File, Rank}, game::CastlingRights, pieces::{Piece, PieceType}};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+	FieldCount(usize),
+	RankCount(usize),
+	RankLength(Rank),
+	Piece(char),
+	SideToMove(String),
+	CastlingRights(String),
+	EnPassant(String),
+	HalfmoveClock(String),
+	FullmoveNumber(String),
+}
+
+impl Display for FenError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FenError::FieldCount(count) => write!(f, "Expected 4 or 6 fields but found {}!", count),
+			FenError::RankCount(count) => write!(f, "Expected 8 ranks but found {}!", count),
+			FenError::RankLength(rank) => write!(f, "Rank {} does not describe 8 squares!", <Rank as Into<char>>::into(*rank)),
+			FenError::Piece(char) => write!(f, "Invalid piece '{}'!", char),
+			FenError::SideToMove(field) => write!(f, "Invalid side to move '{}'!", field),
+			FenError::CastlingRights(field) => write!(f, "Invalid castling rights '{}'!", field),
+			FenError::EnPassant(field) => write!(f, "Invalid en passant square '{}'!", field),
+			FenError::HalfmoveClock(field) => write!(f, "Invalid halfmove clock '{}'!", field),
+			FenError::FullmoveNumber(field) => write!(f, "Invalid fullmove number '{}'!", field),
+		}
+	}
+}
+
+impl Board {
+	pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+		let fields: Vec<&str> = fen.split_whitespace().collect();
+		// The move counters are often left off by other tools, so only the first four are required
+		if fields.len() != 6 && fields.len() != 4 {
+			return Err(FenError::FieldCount(fields.len()));
+		}
+		let mut board = Board::default();
+		let ranks: Vec<&str> = fields[0].split('/').collect();
+		if ranks.len() != 8 {
+			return Err(FenError::RankCount(ranks.len()));
+		}
+		for (placement, rank_index) in ranks.iter().zip((0..8u8).rev()) {
+			let rank = Rank::try_from(rank_index).unwrap();
+			let mut file_index = 0u8;
+			for char in placement.chars() {
+				if let Some(empty) = char.to_digit(10).filter(|empty| (1..=8).contains(empty)) {
+					for _ in 0..empty {
+						let file = File::try_from(file_index).map_err(|_| FenError::RankLength(rank))?;
+						board.get_square_mut(Coordinate { file, rank }).piece = None;
+						file_index += 1;
+					}
+					continue;
+				}
+				let color = if char.is_ascii_uppercase() { Color::White } else { Color::Black };
+				let piece_type = match char.to_ascii_lowercase() {
+					'p' => PieceType::Pawn,
+					'n' => PieceType::Knight,
+					'b' => PieceType::Bishop,
+					'r' => PieceType::Rook,
+					'q' => PieceType::Queen,
+					'k' => PieceType::King,
+					_ => return Err(FenError::Piece(char)),
+				};
+				let file = File::try_from(file_index).map_err(|_| FenError::RankLength(rank))?;
+				board.get_square_mut(Coordinate { file, rank }).piece = Some(Piece::new(color, piece_type));
+				file_index += 1;
+			}
+			if file_index != 8 {
+				return Err(FenError::RankLength(rank));
+			}
+		}
+		board.side_to_move = match fields[1] {
+			"w" => Color::White,
+			"b" => Color::Black,
+			field => return Err(FenError::SideToMove(field.to_string())),
+		};
+		board.castling_rights = (CastlingRights::None, CastlingRights::None);
+		if fields[2] != "-" {
+			let mut seen = String::new();
+			for char in fields[2].chars() {
+				if seen.contains(char) {
+					return Err(FenError::CastlingRights(fields[2].to_string()));
+				}
+				seen.push(char);
+				let (rights, side) = match char {
+					'K' => (&mut board.castling_rights.0, CastlingRights::King),
+					'Q' => (&mut board.castling_rights.0, CastlingRights::Queen),
+					'k' => (&mut board.castling_rights.1, CastlingRights::King),
+					'q' => (&mut board.castling_rights.1, CastlingRights::Queen),
+					_ => return Err(FenError::CastlingRights(fields[2].to_string())),
+				};
+				*rights = match *rights {
+					CastlingRights::None => side,
+					_ => CastlingRights::Both,
+				};
+			}
+		}
+		board.can_passant = match fields[3] {
+			"-" => None,
+			field => {
+				let chars: Vec<char> = field.chars().collect();
+				let expected_rank = match board.side_to_move {
+					Color::White => Rank::Six,
+					Color::Black => Rank::Three,
+				};
+				let coordinate = match chars[..] {
+					[file, rank] => File::try_from(file).ok().zip(Rank::try_from(rank).ok()),
+					_ => None,
+				};
+				match coordinate {
+					Some((file, rank)) if rank == expected_rank => Some(Coordinate { file, rank }),
+					_ => return Err(FenError::EnPassant(field.to_string())),
+				}
+			},
+		};
+		if fields.len() == 6 {
+			board.halfmove_clock = fields[4].parse().map_err(|_| FenError::HalfmoveClock(fields[4].to_string()))?;
+			board.fullmove_number = match fields[5].parse() {
+				Ok(number) if number > 0 => number,
+				_ => return Err(FenError::FullmoveNumber(fields[5].to_string())),
+			};
+		}
+		board.rehash();
+		Ok(board)
+	}
+	pub fn to_fen(&self) -> String {
+		let placement: Vec<String> = self.squares.iter().rev().map(|rank| {
+			let mut rank_string = String::new();
+			let mut empty = 0;
+			for square in rank {
+				match &square.piece {
+					Some(piece) => {
+						if empty > 0 {
+							rank_string.push_str(&empty.to_string());
+							empty = 0;
+						}
+						rank_string.push(piece.fen_char());
+					},
+					None => empty += 1,
+				}
+			}
+			if empty > 0 {
+				rank_string.push_str(&empty.to_string());
+			}
+			rank_string
+		}).collect();
+		let side_to_move = match self.side_to_move {
+			Color::White => "w",
+			Color::Black => "b",
+		};
+		let mut castling = String::new();
+		for (rights, king, queen) in [(self.castling_rights.0, 'K', 'Q'), (self.castling_rights.1, 'k', 'q')] {
+			if matches!(rights, CastlingRights::Both | CastlingRights::King) {
+				castling.push(king);
+			}
+			if matches!(rights, CastlingRights::Both | CastlingRights::Queen) {
+				castling.push(queen);
+			}
+		}
+		if castling.is_empty() {
+			castling.push('-');
+		}
+		let passant = self.can_passant.map_or("-".to_string(), |coordinate| coordinate.to_string());
+		format!("{} {} {} {} {} {}", placement.join("/"), side_to_move, castling, passant, self.halfmove_clock, self.fullmove_number)
+	}
+}
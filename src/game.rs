@@ -1,14 +1,21 @@
-use std::fmt::{self, Display};
+use std::{cell::RefCell, fmt::{self, Display}, io::{stdin, stdout, BufRead, Write}, rc::Rc, time::Duration};
 
-use text_io::read;
-
-use crate::{board::{Board, Color, Coordinate, File, Rank}, pieces::PieceType};
+use crate::{board::{Board, Color, Coordinate, File, MoveUndo, Rank}, clock::{Clock, SystemClock, TimeControl}, controller::{Action, PlayerController, Terminal, TerminalHuman}, fen::FenError, pieces::PieceType};
 
 pub struct Game {
 	pub board: Board,
 	pub turns: Vec<Turn>,
 	pub players: (Player, Player),
-	pub winner: Option<Color>
+	pub result: GameResult,
+	pub termination: Option<Termination>,
+	pub clock: Option<Clock>,
+	pub tags: Vec<(String, String)>,
+	// Hash of every position reached, including the starting one
+	pub positions: Vec<u64>,
+	// What each move overwrote and both clocks before it, for taking moves back
+	history: Vec<(MoveUndo, (Duration, Duration))>,
+	// Moves taken back, most recent last, for replaying them
+	undone: Vec<Move>,
 }
 
 impl Game {
@@ -26,99 +33,399 @@ impl Game {
 		turn_strings.join("; ")
 	}
 	pub fn new(time: u32) -> Self {
-		Self {
+		let tags = [
+			("Event", "?"),
+			("Site", "?"),
+			("Date", "????.??.??"),
+			("Round", "?"),
+			("White", "?"),
+			("Black", "?"),
+		].iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+		let mut game = Self {
 			board: Board::default(),
 			turns: Vec::<Turn>::new(),
 			players: (Player::new(Color::White, time), Player::new(Color::Black, time)),
-			winner: None,
+			result: GameResult::Ongoing,
+			termination: None,
+			clock: None,
+			tags,
+			positions: Vec::new(),
+			history: Vec::new(),
+			undone: Vec::new(),
+		};
+		game.positions.push(game.board.zobrist());
+		// PGN writes "-" for a game without a time control
+		game.set_tag("TimeControl", &if time > 0 { time.to_string() } else { "-".to_string() });
+		if time > 0 {
+			game.clock = Some(Clock::new(TimeControl::sudden_death(Duration::from_secs(time as u64)), SystemClock::new()));
 		}
+		game
 	}
-	pub fn start(&mut self) {
-		loop {
-			println!("{}", self.turns_to_string());
-			let mut new_turn = Turn(None, None);
-			let board = &mut self.board;
-			let (white, black) = &mut self.players;
-
-			let white_king = board.squares.iter().any(|rank| rank.iter().any(|sq| {
-				if let Some(piece) = &sq.piece {
-					if piece.piece_type == PieceType::King && piece.color == Color::White {
-						return true;
-					}
-				}
-				false
-			}));
-			if !white_king {
-				self.winner = Some(black.color);
-				break;
+	pub fn set_clock(&mut self, clock: Clock) {
+		let base = clock.control.stage(0).base;
+		self.players.0.time = base;
+		self.players.1.time = base;
+		self.set_tag("TimeControl", &clock.control.to_string());
+		self.clock = Some(clock);
+	}
+	// Ends the game if the side to move has run out of time, which can only be a draw when the opponent could never mate
+	pub fn check_flag(&mut self) -> bool {
+		let color = self.board.side_to_move;
+		let player = match color {
+			Color::White => &self.players.0,
+			Color::Black => &self.players.1,
+		};
+		let flagged = !self.is_over() && self.clock.as_ref().is_some_and(|clock| clock.is_flagged(player));
+		if flagged {
+			if self.board.has_mating_material(color.opponent()) {
+				self.end(GameResult::win(color.opponent()), Termination::Timeout);
+			} else {
+				self.end(GameResult::Draw, Termination::Timeout);
 			}
-			loop {
-				println!("{}", (*board).to_string(white.color.into()));
-				print!("Enter move for white: ");
-				let white_input: String = read!();
-				let white_move = Move::try_from((white_input.as_str(), white.color));
-				match white_move {
-					Ok(chess_move) => {
-						match board.execute_move(&chess_move, white) {
-								Ok(_) => {
-									new_turn.0 = Some(chess_move);
-									break;
-								},
-								Err(err) => {
-									println!("{}", err.as_str());
-									continue;
-								},
-							}
-					},
-					Err(_) => {
-						println!("Sorry, we couldn't parse that notation");
-						continue;
-					},
-				}
+		}
+		flagged
+	}
+	pub fn from_fen(fen: &str, time: u32) -> Result<Self, FenError> {
+		let mut game = Self::new(time);
+		game.board = Board::from_fen(fen)?;
+		game.positions = vec![game.board.zobrist()];
+		if game.board.is_insufficient_material() {
+			game.end(GameResult::Draw, Termination::InsufficientMaterial);
+		}
+		game.set_tag("SetUp", "1");
+		game.set_tag("FEN", fen);
+		Ok(game)
+	}
+	pub fn tag(&self, name: &str) -> Option<&str> {
+		self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+	}
+	pub fn set_tag(&mut self, name: &str, value: &str) {
+		match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+			Some(tag) => tag.1 = value.to_string(),
+			None => self.tags.push((name.to_string(), value.to_string())),
+		}
+	}
+	pub fn play_move(&mut self, mut chess_move: Move) -> Result<(), MoveError> {
+		self.check_flag();
+		if self.is_over() {
+			return Err(MoveError::GameOver);
+		}
+		let color = self.board.side_to_move;
+		let (player, opponent) = match color {
+			Color::White => (&mut self.players.0, &mut self.players.1),
+			Color::Black => (&mut self.players.1, &mut self.players.0),
+		};
+		let san = self.board.to_san(&chess_move);
+		let times = (player.time, opponent.time);
+		let undo = self.board.execute_move(&chess_move, player)?;
+		opponent.set_checked(self.board.is_in_check(opponent.color));
+		let has_legal_move = self.board.has_legal_move(opponent.color);
+		chess_move.notation = san;
+		chess_move.is_check = opponent.is_checked();
+		chess_move.is_mate = chess_move.is_check && !has_legal_move;
+		match (color, self.turns.last_mut()) {
+			(Color::Black, Some(turn)) if turn.1.is_none() => turn.1 = Some(chess_move),
+			(Color::Black, _) => self.turns.push(Turn(None, Some(chess_move))),
+			(Color::White, _) => self.turns.push(Turn(Some(chess_move), None)),
+		}
+		self.positions.push(self.board.zobrist());
+		let times = match color {
+			Color::White => times,
+			Color::Black => (times.1, times.0),
+		};
+		self.history.push((undo, times));
+		self.undone.clear();
+		if let Some(clock) = &mut self.clock {
+			clock.complete_move(player);
+		}
+		if !has_legal_move {
+			if opponent.is_checked() {
+				self.end(GameResult::win(color), Termination::Checkmate);
+			} else {
+				self.end(GameResult::Draw, Termination::Stalemate);
 			}
-
-			let black_king = board.squares.iter().any(|rank| rank.iter().any(|sq| {
-				if let Some(piece) = &sq.piece {
-					if piece.piece_type == PieceType::King && piece.color == Color::Black {
-						return true;
-					}
-				}
-				false
-			}));
-			if !black_king {
-				self.winner = Some(white.color);
+		} else if self.board.is_insufficient_material() {
+			self.end(GameResult::Draw, Termination::InsufficientMaterial);
+		} else if self.repetition_count() >= 5 {
+			self.end(GameResult::Draw, Termination::FivefoldRepetition);
+		} else if self.board.halfmove_clock >= 150 {
+			self.end(GameResult::Draw, Termination::SeventyFiveMove);
+		}
+		Ok(())
+	}
+	// Plays a move from a record, which may carry on past a draw the rules make automatic, keeping that draw as the result
+	pub(crate) fn replay_move(&mut self, chess_move: Move) -> Result<(), MoveError> {
+		let ending = (self.result, self.termination);
+		if matches!(self.termination, Some(Termination::FivefoldRepetition | Termination::SeventyFiveMove)) {
+			self.result = GameResult::Ongoing;
+		}
+		let played = self.play_move(chess_move);
+		if ending.0 != GameResult::Ongoing {
+			(self.result, self.termination) = ending;
+		}
+		played
+	}
+	// Takes back the last move played, reopening the game if the position it reached ended it
+	pub fn undo(&mut self) -> Result<Move, MoveError> {
+		if self.history.is_empty() {
+			return Err(if self.is_over() { MoveError::GameOver } else { MoveError::NothingToUndo });
+		}
+		match self.termination {
+			None | Some(Termination::Checkmate | Termination::Stalemate | Termination::InsufficientMaterial | Termination::ThreefoldRepetition | Termination::FivefoldRepetition | Termination::FiftyMove | Termination::SeventyFiveMove) => {},
+			Some(_) => return Err(MoveError::GameOver),
+		}
+		let (undo, times) = self.history.pop().unwrap();
+		let turn = self.turns.last_mut().unwrap();
+		let chess_move = turn.1.take().or_else(|| turn.0.take()).unwrap();
+		if turn.0.is_none() && turn.1.is_none() {
+			self.turns.pop();
+		}
+		self.board.unmake_move(&chess_move, undo);
+		self.positions.pop();
+		self.players.0.time = times.0;
+		self.players.1.time = times.1;
+		self.players.0.set_checked(self.board.is_in_check(Color::White));
+		self.players.1.set_checked(self.board.is_in_check(Color::Black));
+		if let Some(clock) = &mut self.clock {
+			clock.take_back_move(self.board.side_to_move);
+		}
+		self.result = GameResult::Ongoing;
+		self.termination = None;
+		self.undone.push(chess_move.clone());
+		Ok(chess_move)
+	}
+	// Plays the last move taken back again
+	pub fn redo(&mut self) -> Result<(), MoveError> {
+		let chess_move = self.undone.pop().ok_or(MoveError::NothingToRedo)?;
+		let undone = std::mem::take(&mut self.undone);
+		let played = self.play_move(chess_move.clone());
+		self.undone = undone;
+		if played.is_err() {
+			self.undone.push(chess_move);
+		}
+		played
+	}
+	// Takes back several moves at once, or none if there are not that many
+	pub fn take_back(&mut self, plies: usize) -> Result<(), MoveError> {
+		if self.history.len() < plies {
+			return Err(MoveError::NothingToUndo);
+		}
+		for _ in 0..plies {
+			self.undo()?;
+		}
+		Ok(())
+	}
+	pub fn is_over(&self) -> bool {
+		self.result != GameResult::Ongoing
+	}
+	// The first way a game ends is the one that counts
+	pub fn end(&mut self, result: GameResult, termination: Termination) {
+		if !self.is_over() {
+			self.result = result;
+			self.termination = Some(termination);
+		}
+	}
+	// A recorded result need not say how the game ended
+	pub fn end_recorded(&mut self, result: GameResult, termination: Option<Termination>) {
+		match termination {
+			Some(termination) => self.end(result, termination),
+			None if !self.is_over() => self.result = result,
+			None => {},
+		}
+	}
+	pub fn resign(&mut self, color: Color) {
+		self.end(GameResult::win(color.opponent()), Termination::Resignation);
+	}
+	pub fn agree_draw(&mut self) {
+		self.end(GameResult::Draw, Termination::Agreement);
+	}
+	pub fn abandon(&mut self, color: Color) {
+		self.end(GameResult::win(color.opponent()), Termination::Abandonment);
+	}
+	// How often the current position has occurred, counting this occurrence
+	pub fn repetition_count(&self) -> usize {
+		let Some(current) = self.positions.last() else { return 0 };
+		// Nothing before the last capture or pawn move can come back
+		self.positions.iter().rev()
+			.take(self.board.halfmove_clock as usize + 1)
+			.filter(|hash| *hash == current)
+			.count()
+	}
+	pub fn claimable_draw(&self) -> Option<Termination> {
+		if self.is_over() {
+			None
+		} else if self.repetition_count() >= 3 {
+			Some(Termination::ThreefoldRepetition)
+		} else if self.board.halfmove_clock >= 100 {
+			Some(Termination::FiftyMove)
+		} else {
+			None
+		}
+	}
+	pub fn claim_draw(&mut self) -> Option<Termination> {
+		let termination = self.claimable_draw()?;
+		self.end(GameResult::Draw, termination);
+		Some(termination)
+	}
+	// Asks each side's controller for its move until the game ends
+	pub fn play<'a>(&mut self, white: &mut (dyn PlayerController + 'a), black: &mut (dyn PlayerController + 'a)) {
+		if let Some(clock) = &mut self.clock {
+			clock.restart_turn();
+		}
+		while !self.is_over() {
+			let color = self.board.side_to_move;
+			let (controller, opponent) = match color {
+				Color::White => (&mut *white, &mut *black),
+				Color::Black => (&mut *black, &mut *white),
+			};
+			let action = controller.choose(self);
+			if self.check_flag() {
 				break;
 			}
-			loop {
-				println!("{}", (*board).to_string(black.color.into()));
-				print!("Enter move for black: ");
-				let black_input: String = read!();
-				let black_move = Move::try_from((black_input.as_str(), black.color));
-				match black_move {
-					Ok(chess_move) => {
-						match board.execute_move(&chess_move, black) {
-								Ok(_) => {
-									new_turn.1 = Some(chess_move);
-									break;
-								},
-								Err(err) => {
-									println!("{}", err.as_str());
-									continue;
-								},
-							}
-					},
-					Err(_) => {
-						println!("Sorry, we couldn't parse that notation");
-						continue;
-					},
-				}
+			match action {
+				Action::Move(chess_move) => {
+					if let Err(err) = self.play_move(chess_move) {
+						controller.rejected(self, err);
+					}
+				},
+				Action::ClaimDraw => {
+					if self.claim_draw().is_none() {
+						controller.rejected(self, MoveError::Invalid);
+					}
+				},
+				Action::Takeback(takeback) => {
+					let taken_back = if self.history.len() < takeback.plies() {
+						Err(MoveError::NothingToUndo)
+					} else if opponent.takeback_offered(self, takeback) {
+						self.take_back(takeback.plies())
+					} else {
+						Err(MoveError::TakebackDeclined)
+					};
+					if let Err(err) = taken_back {
+						controller.rejected(self, err);
+					}
+				},
+				Action::Resign => self.resign(color),
+				Action::Abandon => self.abandon(color),
 			}
-			self.turns.push(new_turn);
 		}
-		println!("{} wins the game!", self.winner.unwrap());
+	}
+	pub fn start(&mut self) {
+		self.run(stdin().lock(), stdout());
+	}
+	// Two people at one terminal, reading moves from the input and writing everything to the output
+	pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: W) {
+		let terminal = Rc::new(RefCell::new(Terminal::new(input, output)));
+		self.play(&mut TerminalHuman::new(terminal.clone()), &mut TerminalHuman::new(terminal.clone()));
+		let mut terminal = terminal.borrow_mut();
+		terminal.write_line(&self.turns_to_string());
+		match (self.result.winner(), self.termination) {
+			(Some(winner), Some(Termination::Checkmate)) => terminal.write_line(&format!("Checkmate, {} wins the game!", winner)),
+			(Some(winner), Some(termination)) => terminal.write_line(&format!("{} wins by {}!", winner, termination.as_str())),
+			(None, Some(termination)) => terminal.write_line(&format!("Game drawn by {}!", termination.as_str())),
+			(_, None) => {},
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameResult {
+	WhiteWins,
+	BlackWins,
+	Draw,
+	Ongoing,
+}
+
+impl GameResult {
+	pub fn win(color: Color) -> Self {
+		match color {
+			Color::White => GameResult::WhiteWins,
+			Color::Black => GameResult::BlackWins,
+		}
+	}
+	pub fn winner(&self) -> Option<Color> {
+		match self {
+			GameResult::WhiteWins => Some(Color::White),
+			GameResult::BlackWins => Some(Color::Black),
+			GameResult::Draw | GameResult::Ongoing => None,
+		}
+	}
+	// The PGN result token
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			GameResult::WhiteWins => "1-0",
+			GameResult::BlackWins => "0-1",
+			GameResult::Draw => "1/2-1/2",
+			GameResult::Ongoing => "*",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Termination {
+	Checkmate,
+	Resignation,
+	Timeout,
+	Stalemate,
+	ThreefoldRepetition,
+	FivefoldRepetition,
+	FiftyMove,
+	SeventyFiveMove,
+	InsufficientMaterial,
+	Agreement,
+	Abandonment,
+}
+
+impl Termination {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Termination::Checkmate => "checkmate",
+			Termination::Resignation => "resignation",
+			Termination::Timeout => "timeout",
+			Termination::Stalemate => "stalemate",
+			Termination::ThreefoldRepetition => "threefold repetition",
+			Termination::FivefoldRepetition => "fivefold repetition",
+			Termination::FiftyMove => "the fifty-move rule",
+			Termination::SeventyFiveMove => "the seventy-five-move rule",
+			Termination::InsufficientMaterial => "insufficient material",
+			Termination::Agreement => "agreement",
+			Termination::Abandonment => "abandonment",
+		}
+	}
+	// Value of the PGN Termination tag
+	pub fn pgn_tag(&self) -> &'static str {
+		match self {
+			Termination::Timeout => "time forfeit",
+			Termination::Abandonment => "abandoned",
+			_ => "normal",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Takeback {
+	// The last move alone
+	Ply,
+	// The last move of each side
+	Turn,
+}
+
+impl Takeback {
+	pub fn plies(&self) -> usize {
+		match self {
+			Takeback::Ply => 1,
+			Takeback::Turn => 2,
+		}
+	}
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Takeback::Ply => "the last move",
+			Takeback::Turn => "the last turn",
+		}
 	}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CastlingRights {
 	Both,
 	Queen,
@@ -126,19 +433,31 @@ pub enum CastlingRights {
 	None
 }
 
+impl CastlingRights {
+	pub fn remove(&mut self, rights: CastlingRights) {
+		*self = match (*self, rights) {
+			(_, CastlingRights::Both) => CastlingRights::None,
+			(CastlingRights::Both, CastlingRights::Queen) => CastlingRights::King,
+			(CastlingRights::Both, CastlingRights::King) => CastlingRights::Queen,
+			(CastlingRights::Queen, CastlingRights::King) | (CastlingRights::King, CastlingRights::Queen) => *self,
+			(_, CastlingRights::None) => *self,
+			_ => CastlingRights::None,
+		}
+	}
+}
+
 pub struct Player {
-	pub castling_rights: CastlingRights,
 	pub color: Color,
 	pub is_checked: bool,
-	pub time: u32,
+	pub time: Duration,
 }
 
 impl Player {
-	pub fn increment_time(&mut self, time: u32) {
+	pub fn increment_time(&mut self, time: Duration) {
 		self.time += time;
 	}
-	pub fn decrement_time(&mut self, time: u32) {
-		self.time -= time;
+	pub fn decrement_time(&mut self, time: Duration) {
+		self.time = self.time.saturating_sub(time);
 	}
 	pub fn is_checked(&self) -> bool {
 		self.is_checked
@@ -149,26 +468,11 @@ impl Player {
 	pub fn color(&self) -> Color {
 		self.color
 	}
-	pub fn remove_castling_rights(&mut self, rights: CastlingRights) {
-		match rights {
-			CastlingRights::Both => self.castling_rights = CastlingRights::None,
-			CastlingRights::Queen => match self.castling_rights {
-				CastlingRights::Both => self.castling_rights = CastlingRights::King,
-				_ => self.castling_rights = CastlingRights::None,
-			},
-			CastlingRights::King => match self.castling_rights {
-				CastlingRights::Both => self.castling_rights = CastlingRights::Queen,
-				_ => self.castling_rights = CastlingRights::None,
-			},
-			_ => {},
-		}
-	}
 	pub fn new(color: Color, time: u32) -> Self {
 		Self {
-			castling_rights: CastlingRights::Both,
 			color,
 			is_checked: false,
-			time,
+			time: Duration::from_secs(time as u64),
 		}
 	}
 }
@@ -176,7 +480,7 @@ impl Player {
 const KINGSIDE_CASTLE: &str = "O-O";
 const QUEENSIDE_CASTLE: &str = "O-O-O";
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Move {
 	pub notation: String,
 	pub from: Coordinate,
@@ -188,10 +492,69 @@ pub struct Move {
 	pub is_mate: bool,
 }
 
+impl Move {
+	pub fn new(piece_type: PieceType, from: Coordinate, to: Coordinate, move_type: MoveType, promotion: Option<PieceType>) -> Self {
+		let notation = match move_type {
+			MoveType::CastleKingSide => KINGSIDE_CASTLE.to_string(),
+			MoveType::CastleQueenSide => QUEENSIDE_CASTLE.to_string(),
+			MoveType::Promotion => format!("{}{}={}", from, to, promotion.map_or("", |pt| pt.notation())),
+			MoveType::Normal => format!("{}{}{}", piece_type.notation(), from, to),
+		};
+		Self {
+			notation,
+			from,
+			to,
+			piece_type,
+			promotion,
+			move_type,
+			is_check: false,
+			is_mate: false,
+		}
+	}
+	pub fn from_uci(uci: &str, board: &Board) -> Result<Self, MoveError> {
+		let chars: Vec<char> = uci.chars().collect();
+		if chars.len() != 4 && chars.len() != 5 {
+			return Err(MoveError::Notation);
+		}
+		let coordinate = |file: char, rank: char| Some(Coordinate {
+			file: File::try_from(file).ok()?,
+			rank: Rank::try_from(rank).ok()?
+		});
+		let (from, to) = match (coordinate(chars[0], chars[1]), coordinate(chars[2], chars[3])) {
+			(Some(from), Some(to)) => (from, to),
+			_ => return Err(MoveError::Notation),
+		};
+		let promotion = match chars.get(4) {
+			None => None,
+			Some('q') => Some(PieceType::Queen),
+			Some('r') => Some(PieceType::Rook),
+			Some('b') => Some(PieceType::Bishop),
+			Some('n') => Some(PieceType::Knight),
+			Some(_) => return Err(MoveError::Notation),
+		};
+		let mut chess_move = board.legal_moves(board.side_to_move).into_iter()
+			.find(|candidate| candidate.from == from && candidate.to == to && candidate.promotion == promotion)
+			.ok_or(MoveError::Invalid)?;
+		chess_move.notation = uci.to_string();
+		Ok(chess_move)
+	}
+	// The same move on the board, whatever notation it was written in
+	pub fn is_same(&self, other: &Move) -> bool {
+		self.from == other.from && self.to == other.to && self.promotion == other.promotion
+	}
+	pub fn to_uci(&self) -> String {
+		let promotion = self.promotion.map_or(String::new(), |promotion| promotion.notation().to_lowercase());
+		format!("{}{}{}", self.from, self.to, promotion)
+	}
+}
+
 impl TryFrom<(&str, Color)> for Move {
     type Error = &'static str;
 
 	fn try_from((value, color): (&str, Color)) -> Result<Self, Self::Error> {
+		if value.is_empty() {
+			return Err(MoveError::Notation.as_str());
+		}
 		let chars = value.as_bytes();
 		let piece_type = match PieceType::try_from(char::from_u32(chars[0] as u32).unwrap()) {
 			Ok(pt) => pt,
@@ -260,16 +623,17 @@ impl TryFrom<(&str, Color)> for Move {
 				}
 			},
 			_ => {
-				if piece_type == PieceType::Pawn {
-					(
-						Coordinate::from((chars[0] as char, chars[1] as char)),
-						Coordinate::from((chars[2] as char, chars[3] as char))
-					)
-				} else {
-					(
-						Coordinate::from((chars[1] as char, chars[2] as char)),
-						Coordinate::from((chars[3] as char, chars[4] as char))
-					)
+				let offset = if piece_type == PieceType::Pawn { 0 } else { 1 };
+				let coordinate = |index: usize| {
+					let (file, rank) = (*chars.get(index)? as char, *chars.get(index + 1)? as char);
+					Some(Coordinate {
+						file: File::try_from(file).ok()?,
+						rank: Rank::try_from(rank).ok()?
+					})
+				};
+				match (coordinate(offset), coordinate(offset + 2)) {
+					(Some(from), Some(to)) => (from, to),
+					_ => return Err(MoveError::Notation.as_str()),
 				}
 			}
 		};
@@ -302,7 +666,9 @@ impl Default for Move {
 	}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MoveError {
+	Ambiguous,
 	Blocked,
 	InCheck,
 	Invalid,
@@ -312,11 +678,16 @@ pub enum MoveError {
 	PawnNoCapture,
 	PromotionRank,
 	CastlingRights,
+	GameOver,
+	NothingToUndo,
+	NothingToRedo,
+	TakebackDeclined,
 }
 
 impl MoveError {
 	pub fn as_str(&self) -> &'static str {
 		match self {
+			MoveError::Ambiguous => "Move is ambiguous!",
 			MoveError::InCheck => "King is in check!",
 			MoveError::Invalid => "Move is invalid!",
 			MoveError::Pinned => "Piece is currently pinned!",
@@ -326,11 +697,15 @@ impl MoveError {
 			MoveError::PromotionRank => "Cannot promote on this rank!",
 			MoveError::Blocked => "Movement is blocked!",
 			MoveError::CastlingRights => "Castling rights are invalid!",
+			MoveError::GameOver => "Game is over!",
+			MoveError::NothingToUndo => "No move to take back!",
+			MoveError::NothingToRedo => "No move to redo!",
+			MoveError::TakebackDeclined => "Takeback was declined!",
 		}
 	}
 }
 
-#[derive(Clone, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub enum MoveType {
 	#[default]
 	Normal,
@@ -363,18 +738,20 @@ impl TryFrom<&str> for MoveType {
 			return Ok(MoveType::CastleKingSide);
 		}
 		let piece_type = PieceType::try_from(value.chars().next().unwrap());
-		if let Ok(_) = piece_type { return Ok(MoveType::Normal) };
+		if piece_type.is_ok() { return Ok(MoveType::Normal) };
 		Err(MoveError::Notation)
 	}
 }
 
-pub struct Turn(Option<Move>, Option<Move>);
+pub struct Turn(pub Option<Move>, pub Option<Move>);
 
 impl fmt::Display for Turn {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "{} {}",
-			self.0.clone().unwrap_or(Move::default()).notation,
-			self.1.clone().unwrap_or(Move::default()).notation
-		)
+		match (&self.0, &self.1) {
+			(Some(white), Some(black)) => write!(f, "{} {}", white.notation, black.notation),
+			(Some(white), None) => write!(f, "{}", white.notation),
+			(None, Some(black)) => write!(f, "... {}", black.notation),
+			(None, None) => Ok(()),
+		}
 	}
 }
\ No newline at end of file
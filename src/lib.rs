@@ -1,4 +1,17 @@
 pub mod board;
+pub mod clock;
+pub mod controller;
+pub mod eval;
+pub mod fen;
 pub mod game;
+pub mod pgn;
 pub mod pieces;
-pub mod tests;
\ No newline at end of file
+pub mod position;
+pub mod san;
+pub mod search;
+pub mod tests;
+pub mod tree;
+pub mod tt;
+pub mod uci;
+pub mod xboard;
+pub mod zobrist;
\ No newline at end of file
@@ -0,0 +1,15 @@
+use std::io::{stdin, stdout, BufRead, Read};
+
+fn main() {
+	let mut input = stdin().lock();
+	let mut first_line = String::new();
+	if input.read_line(&mut first_line).is_err() {
+		return;
+	}
+	// Xboard announces itself with its first command; everything else is treated as UCI
+	if first_line.trim() == "xboard" {
+		chess_rs::xboard::run(input, stdout());
+	} else {
+		chess_rs::uci::run(first_line.as_bytes().chain(input), stdout());
+	}
+}
@@ -0,0 +1,366 @@
+use std::{fmt::{self, Display}, io::{BufRead, Lines}};
+
+use crate::{board::Color, fen::FenError, game::{Game, GameResult, Move, MoveError, Termination}, tree::{GameTree, Node}};
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const LINE_LENGTH: usize = 79;
+// Move suffixes standing for the first six numeric annotation glyphs
+const NAG_GLYPHS: [&str; 6] = ["!", "?", "!!", "??", "!?", "?!"];
+
+impl Game {
+	pub fn result_token(&self) -> &'static str {
+		self.result.as_str()
+	}
+	pub fn to_pgn(&self) -> String {
+		GameTree::from(self).to_pgn()
+	}
+}
+
+impl GameTree {
+	pub fn to_pgn(&self) -> String {
+		let result = self.result.as_str();
+		let tag = |name: &str| self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str());
+		let mut pgn = String::new();
+		let tag_line = |name: &str, value: &str| {
+			format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+		};
+		for name in SEVEN_TAG_ROSTER {
+			let value = if name == "Result" { result } else { tag(name).unwrap_or("?") };
+			pgn.push_str(&tag_line(name, value));
+		}
+		for (name, value) in self.tags.iter().filter(|(name, _)| !SEVEN_TAG_ROSTER.contains(&name.as_str())) {
+			pgn.push_str(&tag_line(name, value));
+		}
+		if let (Some(termination), None) = (self.termination, tag("Termination")) {
+			pgn.push_str(&tag_line("Termination", termination.pgn_tag()));
+		}
+		pgn.push('\n');
+		let mut tokens = Vec::<String>::new();
+		if let Some(comment) = &self.comment {
+			push_comment(&mut tokens, comment);
+		}
+		line_tokens(&self.children, self.start().fullmove_number, self.start().side_to_move, &mut tokens);
+		tokens.push(result.to_string());
+		let mut line = String::new();
+		for token in tokens {
+			if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+				pgn.push_str(&line);
+				pgn.push('\n');
+				line.clear();
+			}
+			if !line.is_empty() {
+				line.push(' ');
+			}
+			line.push_str(&token);
+		}
+		pgn.push_str(&line);
+		pgn.push('\n');
+		pgn
+	}
+}
+
+// Writes a line of moves and, after each move, the variations that could have been played instead
+fn line_tokens(mut nodes: &[Node], mut number: u32, mut color: Color, tokens: &mut Vec<String>) {
+	// Black's move is numbered again after anything that interrupts the line
+	let mut numbered = false;
+	while let Some(node) = nodes.first() {
+		let glyph = node.nags.first().and_then(|nag| NAG_GLYPHS.get((*nag as usize).wrapping_sub(1)));
+		let san = format!("{}{}", node.chess_move.notation, glyph.unwrap_or(&""));
+		match color {
+			Color::White => tokens.push(format!("{}. {}", number, san)),
+			Color::Black if !numbered => tokens.push(format!("{}... {}", number, san)),
+			Color::Black => tokens.push(san),
+		}
+		numbered = true;
+		for nag in node.nags.iter().skip(glyph.is_some() as usize) {
+			tokens.push(format!("${}", nag));
+		}
+		if let Some(comment) = node.full_comment() {
+			push_comment(tokens, &comment);
+			numbered = false;
+		}
+		for variation in &nodes[1..] {
+			let start = tokens.len();
+			line_tokens(std::slice::from_ref(variation), number, color, tokens);
+			tokens[start].insert(0, '(');
+			tokens.last_mut().unwrap().push(')');
+			numbered = false;
+		}
+		if color == Color::Black {
+			number += 1;
+		}
+		color = color.opponent();
+		nodes = &node.children;
+	}
+}
+
+// Comments are split into words so that long ones still wrap
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+	let start = tokens.len();
+	tokens.extend(comment.split_whitespace().map(str::to_string));
+	tokens[start].insert(0, '{');
+	tokens.last_mut().unwrap().push('}');
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnErrorKind {
+	Io(String),
+	Tag,
+	Fen(FenError),
+	Move(String, MoveError),
+	UnbalancedVariation,
+	UnterminatedComment,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnError {
+	pub line: usize,
+	pub column: usize,
+	pub kind: PgnErrorKind,
+}
+
+impl Display for PgnError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Line {}, column {}: ", self.line, self.column)?;
+		match &self.kind {
+			PgnErrorKind::Io(err) => write!(f, "{}", err),
+			PgnErrorKind::Tag => write!(f, "Malformed tag pair!"),
+			PgnErrorKind::Fen(err) => write!(f, "{}", err),
+			PgnErrorKind::Move(san, err) => write!(f, "{} {}", san, err.as_str()),
+			PgnErrorKind::UnbalancedVariation => write!(f, "Unbalanced variation!"),
+			PgnErrorKind::UnterminatedComment => write!(f, "Unterminated comment!"),
+		}
+	}
+}
+
+pub struct PgnReader<R: BufRead> {
+	lines: Lines<R>,
+	line_number: usize,
+	// Line number, column offset and text still to be read, for lines shared between two games
+	pending: Option<(usize, usize, String)>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self {
+			lines: reader.lines(),
+			line_number: 0,
+			pending: None,
+		}
+	}
+	fn next_line(&mut self) -> Option<Result<(usize, usize, String), PgnError>> {
+		if let Some(pending) = self.pending.take() {
+			return Some(Ok(pending));
+		}
+		self.line_number += 1;
+		match self.lines.next()? {
+			Ok(line) => Some(Ok((self.line_number, 0, line))),
+			Err(err) => Some(Err(PgnError { line: self.line_number, column: 1, kind: PgnErrorKind::Io(err.to_string()) })),
+		}
+	}
+	fn read_game(&mut self) -> Result<Option<(Game, GameTree)>, PgnError> {
+		let mut tags = Vec::<(String, String)>::new();
+		let mut game: Option<(Game, GameTree)> = None;
+		// Where an open comment started and its text so far
+		let mut comment: Option<(usize, usize, String)> = None;
+		// The tree cursor to return to at the end of each open variation
+		let mut variations = Vec::<Vec<usize>>::new();
+		// A comment before the first move of a variation, kept for that move
+		let mut leading_comment: Option<String> = None;
+		let mut line_start = true;
+		'lines: while let Some(next) = self.next_line() {
+			let (line_number, offset, line) = next?;
+			let trimmed = line.trim();
+			if comment.is_none() && (trimmed.is_empty() || line.starts_with('%')) {
+				continue;
+			}
+			if comment.is_none() && trimmed.starts_with('[') {
+				if game.is_some() {
+					self.pending = Some((line_number, offset, line));
+					break;
+				}
+				let column = offset + line.len() - line.trim_start().len() + 1;
+				tags.push(parse_tag(trimmed).ok_or(PgnError { line: line_number, column, kind: PgnErrorKind::Tag })?);
+				continue;
+			}
+			let (game, tree) = match &mut game {
+				Some((game, tree)) => (game, tree),
+				None => {
+					let new = new_game(&tags).map_err(|err| PgnError { line: line_number, column: 1, kind: PgnErrorKind::Fen(err) })?;
+					let tree = GameTree::from(&new);
+					let (game, tree) = game.insert((new, tree));
+					(game, tree)
+				},
+			};
+			let chars: Vec<char> = line.chars().collect();
+			let mut index = 0;
+			while index < chars.len() {
+				let column = offset + index + 1;
+				if let Some((_, _, text)) = &mut comment {
+					if chars[index] != '}' {
+						text.push(chars[index]);
+						index += 1;
+						continue;
+					}
+					let text = comment.take().unwrap().2;
+					match (tree.current_mut(), line_start, variations.is_empty()) {
+						(_, true, true) => tree.comment = Some(tree.comment.take().map_or(text.trim().to_string(), |comment| format!("{} {}", comment, text.trim()))),
+						(_, true, false) => leading_comment = Some(text),
+						(Some(node), false, _) => node.annotate(&text),
+						(None, false, _) => {},
+					}
+					index += 1;
+					continue;
+				}
+				match chars[index] {
+					'{' => comment = Some((line_number, column, String::new())),
+					';' => break,
+					'(' => {
+						variations.push(tree.path().to_vec());
+						// A variation replaces the move just played, so there has to be one
+						if !tree.back() {
+							return Err(PgnError { line: line_number, column, kind: PgnErrorKind::UnbalancedVariation });
+						}
+						line_start = true;
+					},
+					')' => {
+						let path = variations.pop().ok_or(PgnError { line: line_number, column, kind: PgnErrorKind::UnbalancedVariation })?;
+						tree.go_to_path(&path);
+						line_start = false;
+					},
+					char if char.is_whitespace() => {},
+					_ => {
+						let start = index;
+						while index < chars.len() && !chars[index].is_whitespace() && !"{}();".contains(chars[index]) {
+							index += 1;
+						}
+						let token: String = chars[start..index].iter().collect();
+						if let Some(nag) = token.strip_prefix('$') {
+							if let (Ok(nag), Some(node)) = (nag.parse(), tree.current_mut()) {
+								node.nags.push(nag);
+							}
+							continue;
+						}
+						if let Some(result) = ["1-0", "0-1", "1/2-1/2", "*"].iter().find(|result| **result == token) {
+							if !variations.is_empty() {
+								continue;
+							}
+							// A decisive result without a mate on the board only says how it ended when the tag does
+							let decisive = match game.tag("Termination") {
+								Some("time forfeit") => Some(Termination::Timeout),
+								Some("abandoned") => Some(Termination::Abandonment),
+								_ => None,
+							};
+							let drawn = game.claimable_draw().unwrap_or(Termination::Agreement);
+							match *result {
+								"1-0" => game.end_recorded(GameResult::WhiteWins, decisive),
+								"0-1" => game.end_recorded(GameResult::BlackWins, decisive),
+								"1/2-1/2" => game.end(GameResult::Draw, drawn),
+								_ => {},
+							}
+							let rest: String = chars[index..].iter().collect();
+							if !rest.trim().is_empty() {
+								self.pending = Some((line_number, offset + index, rest));
+							}
+							break 'lines;
+						}
+						// Move numbers may be attached to the move, as in "12.Nf3" or "12...Nf3"
+						let (san, san_column) = match token.find('.') {
+							Some(dot) if token[..dot].chars().all(|char| char.is_ascii_digit()) => {
+								let san = token[dot..].trim_start_matches('.');
+								(san, column + token.len() - san.len())
+							},
+							_ => (token.as_str(), column),
+						};
+						if san.is_empty() {
+							continue;
+						}
+						let move_error = |err| PgnError { line: line_number, column: san_column, kind: PgnErrorKind::Move(san.to_string(), err) };
+						let chess_move = Move::try_from((san, tree.board())).map_err(move_error)?;
+						// The main line is played out in the game as well, which also ends it where the rules say
+						if variations.is_empty() {
+							game.replay_move(chess_move.clone()).map_err(move_error)?;
+						}
+						tree.play_move(chess_move).map_err(move_error)?;
+						let node = tree.current_mut().unwrap();
+						let glyph = san.trim_start_matches(|char| !"!?".contains(char));
+						if let Some(nag) = NAG_GLYPHS.iter().position(|known| *known == glyph) {
+							node.nags.push(nag as u8 + 1);
+						}
+						if let Some(text) = leading_comment.take() {
+							node.annotate(&text);
+						}
+						line_start = false;
+						continue;
+					},
+				}
+				index += 1;
+			}
+			// Comments spanning lines keep the words on either side of the break apart
+			if let Some((_, _, text)) = &mut comment {
+				text.push(' ');
+			}
+		}
+		if let Some((line, column, _)) = comment {
+			return Err(PgnError { line, column, kind: PgnErrorKind::UnterminatedComment });
+		}
+		if game.is_none() && !tags.is_empty() {
+			let new = new_game(&tags).map_err(|err| PgnError { line: self.line_number, column: 1, kind: PgnErrorKind::Fen(err) })?;
+			let tree = GameTree::from(&new);
+			game = Some((new, tree));
+		}
+		Ok(game.map(|(game, mut tree)| {
+			tree.tags = game.tags.clone();
+			tree.result = game.result;
+			tree.termination = game.termination;
+			tree.go_to_ply(0);
+			(game, tree)
+		}))
+	}
+	// Reads the next game with its variations and annotations
+	pub fn next_tree(&mut self) -> Option<Result<GameTree, PgnError>> {
+		self.read_game().map(|game| game.map(|(_, tree)| tree)).transpose()
+	}
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+	type Item = Result<Game, PgnError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.read_game().map(|game| game.map(|(game, _)| game)).transpose()
+	}
+}
+
+impl Game {
+	pub fn from_pgn(pgn: &str) -> Result<Game, PgnError> {
+		PgnReader::new(pgn.as_bytes()).next().unwrap_or(Ok(Game::new(0)))
+	}
+}
+
+impl GameTree {
+	pub fn from_pgn(pgn: &str) -> Result<GameTree, PgnError> {
+		PgnReader::new(pgn.as_bytes()).next_tree().unwrap_or_else(|| Ok(GameTree::from(&Game::new(0))))
+	}
+}
+
+fn new_game(tags: &[(String, String)]) -> Result<Game, FenError> {
+	// Recorded games are replayed without a running clock, the TimeControl tag is kept as is
+	let mut game = match tags.iter().find(|(name, _)| name == "FEN") {
+		Some((_, fen)) => Game::from_fen(fen, 0)?,
+		None => Game::new(0),
+	};
+	game.tags = tags.to_vec();
+	Ok(game)
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+	let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+	let (name, value) = inner.split_once(char::is_whitespace)?;
+	let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+	let mut unescaped = String::new();
+	let mut chars = value.chars();
+	while let Some(char) = chars.next() {
+		unescaped.push(if char == '\\' { chars.next()? } else { char });
+	}
+	Some((name.to_string(), unescaped))
+}
@@ -16,7 +16,7 @@ const B_BISHOP: char = '♝';
 const B_KNIGHT: char = '♞';
 const B_PAWN: char = '♟';
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Piece {
 	pub color: Color,
 	pub piece_type: PieceType,
@@ -44,6 +44,21 @@ impl Piece {
 		}
 	}
 
+	pub fn fen_char(&self) -> char {
+		let char = match self.piece_type {
+			PieceType::Pawn => 'p',
+			PieceType::Bishop => 'b',
+			PieceType::Knight => 'n',
+			PieceType::Rook => 'r',
+			PieceType::Queen => 'q',
+			PieceType::King => 'k',
+		};
+		match self.color {
+			Color::White => char.to_ascii_uppercase(),
+			Color::Black => char,
+		}
+	}
+
 	pub fn new (color: Color, piece_type: PieceType) -> Self {
 		Self {
 			color, piece_type
@@ -55,7 +70,7 @@ impl Piece {
 	}
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PieceType {
 	Pawn,
 	Bishop,
@@ -65,12 +80,25 @@ pub enum PieceType {
 	King,
 }
 
+impl PieceType {
+	pub fn notation(&self) -> &'static str {
+		match self {
+			PieceType::Pawn => "",
+			PieceType::Bishop => "B",
+			PieceType::Knight => "N",
+			PieceType::Rook => "R",
+			PieceType::Queen => "Q",
+			PieceType::King => "K",
+		}
+	}
+}
+
 impl TryFrom<char> for PieceType {
 	type Error = MoveError;
 
 	fn try_from(char: char) -> Result<Self, Self::Error> {
 		match char {
-			'a' | 'b' | 'c' | 'd' | 'e' | 'f' => Ok(PieceType::Pawn),
+			'a' | 'b' | 'c' | 'd' | 'e' | 'f' | 'g' | 'h' => Ok(PieceType::Pawn),
 			'N' => Ok(PieceType::Knight),
 			'B' => Ok(PieceType::Bishop),
 			'R' => Ok(PieceType::Rook),
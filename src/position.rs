@@ -0,0 +1,91 @@
+use std::fmt::{self, Display};
+
+use crate::{board::{Board, Color, Coordinate}, fen::FenError, game::{CastlingRights, Move, MoveError, Player}, pieces::Piece};
+
+// A FEN-like snapshot of a position that can be copied, compared and hashed, rebuilding a board for anything that needs moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+	// Indexed by rank then file, as the board's squares are
+	pub pieces: [[Option<Piece>; 8]; 8],
+	pub side_to_move: Color,
+	pub castling_rights: (CastlingRights, CastlingRights),
+	pub en_passant: Option<Coordinate>,
+	pub halfmove_clock: u32,
+	pub fullmove_number: u32,
+}
+
+impl Position {
+	pub fn piece(&self, coordinate: Coordinate) -> Option<Piece> {
+		self.pieces[coordinate.rank as usize][coordinate.file as usize]
+	}
+	pub fn set_piece(&mut self, coordinate: Coordinate, piece: Option<Piece>) {
+		self.pieces[coordinate.rank as usize][coordinate.file as usize] = piece;
+	}
+	pub fn castling_rights(&self, color: Color) -> CastlingRights {
+		match color {
+			Color::White => self.castling_rights.0,
+			Color::Black => self.castling_rights.1,
+		}
+	}
+	pub fn zobrist(&self) -> u64 {
+		Board::from(*self).zobrist()
+	}
+	pub fn legal_moves(&self) -> Vec<Move> {
+		Board::from(*self).legal_moves(self.side_to_move)
+	}
+	pub fn after(&self, chess_move: &Move) -> Result<Position, MoveError> {
+		let mut board = Board::from(*self);
+		board.execute_move(chess_move, &mut Player::new(self.side_to_move, 0))?;
+		Ok(Position::from(&board))
+	}
+}
+
+impl Default for Position {
+	fn default() -> Self {
+		Position::from(&Board::default())
+	}
+}
+
+impl From<&Board> for Position {
+	fn from(board: &Board) -> Self {
+		Self {
+			pieces: board.squares.each_ref().map(|rank| rank.each_ref().map(|square| square.piece)),
+			side_to_move: board.side_to_move,
+			castling_rights: board.castling_rights,
+			en_passant: board.can_passant,
+			halfmove_clock: board.halfmove_clock,
+			fullmove_number: board.fullmove_number,
+		}
+	}
+}
+
+impl From<Position> for Board {
+	fn from(position: Position) -> Self {
+		let mut board = Board::default();
+		for square in board.squares.iter_mut().flatten() {
+			square.piece = position.piece(square.coordinate);
+		}
+		board.side_to_move = position.side_to_move;
+		board.castling_rights = position.castling_rights;
+		board.can_passant = position.en_passant;
+		board.halfmove_clock = position.halfmove_clock;
+		board.fullmove_number = position.fullmove_number;
+		board.rehash();
+		board
+	}
+}
+
+impl TryFrom<&str> for Position {
+	type Error = FenError;
+
+	fn try_from(fen: &str) -> Result<Self, Self::Error> {
+		Board::from_fen(fen).map(|board| Position::from(&board))
+	}
+}
+
+// Written as FEN
+impl Display for Position {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", Board::from(*self).to_fen())
+	}
+}
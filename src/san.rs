@@ -0,0 +1,144 @@
+use crate::{board::{Board, Coordinate, File, Rank}, game::{Move, MoveError, MoveType}, pieces::PieceType};
+
+impl Board {
+	pub fn to_san(&self, chess_move: &Move) -> String {
+		let color = self.side_to_move;
+		let mut san = match chess_move.move_type {
+			MoveType::CastleKingSide => "O-O".to_string(),
+			MoveType::CastleQueenSide => "O-O-O".to_string(),
+			_ => {
+				let capture = self.get_square(chess_move.to).piece.is_some()
+					|| (chess_move.piece_type == PieceType::Pawn && self.can_passant == Some(chess_move.to));
+				let mut san = chess_move.piece_type.notation().to_string();
+				if chess_move.piece_type == PieceType::Pawn {
+					if capture {
+						san.push(chess_move.from.file.into());
+					}
+				} else {
+					let rivals: Vec<Coordinate> = self.legal_moves(color).into_iter()
+						.filter(|other| other.piece_type == chess_move.piece_type && other.to == chess_move.to && other.from != chess_move.from)
+						.map(|other| other.from)
+						.collect();
+					if !rivals.is_empty() {
+						if rivals.iter().all(|from| from.file != chess_move.from.file) {
+							san.push(chess_move.from.file.into());
+						} else if rivals.iter().all(|from| from.rank != chess_move.from.rank) {
+							san.push(chess_move.from.rank.into());
+						} else {
+							san.push_str(&chess_move.from.to_string());
+						}
+					}
+				}
+				if capture {
+					san.push('x');
+				}
+				san.push_str(&chess_move.to.to_string());
+				if let Some(promotion) = chess_move.promotion {
+					san.push('=');
+					san.push_str(promotion.notation());
+				}
+				san
+			},
+		};
+		let mut after = self.clone();
+		after.apply_move(chess_move, color);
+		if after.is_in_check(color.opponent()) {
+			san.push(if after.has_legal_move(color.opponent()) { '+' } else { '#' });
+		}
+		san
+	}
+}
+
+impl TryFrom<(&str, &Board)> for Move {
+	type Error = MoveError;
+
+	fn try_from((value, board): (&str, &Board)) -> Result<Self, Self::Error> {
+		let san = value.trim_end_matches(['+', '#', '!', '?']);
+		let castle = match san {
+			"O-O" | "0-0" => Some(MoveType::CastleKingSide),
+			"O-O-O" | "0-0-0" => Some(MoveType::CastleQueenSide),
+			_ => None,
+		};
+		let mut chars: Vec<char> = san.chars().collect();
+		let piece_type = match chars.first() {
+			_ if castle.is_some() => PieceType::King,
+			Some('N') => PieceType::Knight,
+			Some('B') => PieceType::Bishop,
+			Some('R') => PieceType::Rook,
+			Some('Q') => PieceType::Queen,
+			Some('K') => PieceType::King,
+			Some('a'..='h') => PieceType::Pawn,
+			_ => return Err(MoveError::Notation),
+		};
+		if piece_type != PieceType::Pawn {
+			chars.remove(0);
+		}
+		let mut promotion = None;
+		let mut destination = None;
+		let mut from_file = None;
+		let mut from_rank = None;
+		if castle.is_none() {
+			if piece_type == PieceType::Pawn {
+				if let Some(&last) = chars.last().filter(|last| last.is_ascii_uppercase()) {
+					promotion = Some(match last {
+						'N' => PieceType::Knight,
+						'B' => PieceType::Bishop,
+						'R' => PieceType::Rook,
+						'Q' => PieceType::Queen,
+						_ => return Err(MoveError::Notation),
+					});
+					chars.pop();
+					if chars.last() == Some(&'=') {
+						chars.pop();
+					}
+				}
+			}
+			if chars.len() < 2 {
+				return Err(MoveError::Notation);
+			}
+			let rank_char = chars.pop().unwrap();
+			let file_char = chars.pop().unwrap();
+			destination = match (File::try_from(file_char), Rank::try_from(rank_char)) {
+				(Ok(file), Ok(rank)) => Some(Coordinate { file, rank }),
+				_ => return Err(MoveError::Notation),
+			};
+			if chars.last() == Some(&'x') {
+				chars.pop();
+			}
+			for char in chars {
+				if let Ok(file) = File::try_from(char) {
+					if from_file.replace(file).is_some() {
+						return Err(MoveError::Notation);
+					}
+				} else if let Ok(rank) = Rank::try_from(char) {
+					if from_rank.replace(rank).is_some() {
+						return Err(MoveError::Notation);
+					}
+				} else {
+					return Err(MoveError::Notation);
+				}
+			}
+		}
+		let mut candidates = board.legal_moves(board.side_to_move).into_iter().filter(|candidate| {
+			match &castle {
+				Some(move_type) => candidate.move_type == *move_type,
+				None => {
+					candidate.piece_type == piece_type
+						&& !matches!(candidate.move_type, MoveType::CastleKingSide | MoveType::CastleQueenSide)
+						&& Some(candidate.to) == destination
+						&& candidate.promotion == promotion
+						&& from_file.is_none_or(|file| candidate.from.file == file)
+						&& from_rank.is_none_or(|rank| candidate.from.rank == rank)
+				}
+			}
+		});
+		match (candidates.next(), candidates.next()) {
+			(Some(mut chess_move), None) => {
+				chess_move.notation = value.to_string();
+				Ok(chess_move)
+			},
+			(Some(_), Some(_)) => Err(MoveError::Ambiguous),
+			(None, _) => Err(MoveError::Invalid),
+		}
+	}
+}
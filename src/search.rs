@@ -0,0 +1,240 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
+
+use crate::{board::Board, eval::{self, EvalWeights}, game::{Move, MoveType}, pieces::PieceType, tt::{Bound, TranspositionTable, TtMove}};
+
+pub const MATE_SCORE: i32 = 100_000;
+const MAX_DEPTH: u32 = 64;
+const INFINITY: i32 = MATE_SCORE + 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+	pub depth: Option<u32>,
+	pub nodes: Option<u64>,
+	pub time: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+	pub depth: u32,
+	pub score: i32,
+	pub nodes: u64,
+	pub elapsed: Duration,
+	pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+	pub fn best_move(&self) -> Option<&Move> {
+		self.pv.first()
+	}
+	// Moves until mate, negative when the side to move is being mated
+	pub fn mate_in(&self) -> Option<i32> {
+		if self.score.abs() < MATE_SCORE - MAX_DEPTH as i32 * 2 {
+			return None;
+		}
+		let plies = MATE_SCORE - self.score.abs();
+		Some(self.score.signum() * (plies + 1) / 2)
+	}
+}
+
+struct Searcher<'a> {
+	limits: SearchLimits,
+	weights: &'a EvalWeights,
+	tt: &'a mut TranspositionTable,
+	stop: &'a AtomicBool,
+	start: Instant,
+	nodes: u64,
+	aborted: bool,
+}
+
+pub fn search(board: &Board, limits: SearchLimits, weights: &EvalWeights, tt: &mut TranspositionTable, stop: &AtomicBool, mut on_iteration: impl FnMut(&SearchInfo)) -> SearchInfo {
+	tt.new_search();
+	let mut searcher = Searcher {
+		limits,
+		weights,
+		tt,
+		stop,
+		start: Instant::now(),
+		nodes: 0,
+		aborted: false,
+	};
+	let mut best = SearchInfo {
+		depth: 0,
+		score: 0,
+		nodes: 0,
+		elapsed: Duration::ZERO,
+		// Keep a move to play even if the first iteration is cut short
+		pv: board.legal_moves(board.side_to_move).into_iter().take(1).collect(),
+	};
+	for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+		let mut pv = Vec::new();
+		let previous_pv = best.pv.clone();
+		let score = searcher.negamax(board, depth, 0, -INFINITY, INFINITY, &previous_pv, &mut pv);
+		if searcher.aborted {
+			break;
+		}
+		best = SearchInfo {
+			depth,
+			score,
+			nodes: searcher.nodes,
+			elapsed: searcher.start.elapsed(),
+			pv,
+		};
+		on_iteration(&best);
+		if best.mate_in().is_some() || best.pv.is_empty() {
+			break;
+		}
+	}
+	best.nodes = searcher.nodes;
+	best.elapsed = searcher.start.elapsed();
+	best
+}
+
+impl Searcher<'_> {
+	fn should_abort(&mut self) -> bool {
+		if !self.aborted {
+			let out_of_nodes = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes);
+			let out_of_time = self.nodes.is_multiple_of(1024) && self.limits.time.is_some_and(|time| self.start.elapsed() >= time);
+			self.aborted = out_of_nodes || out_of_time || self.stop.load(Ordering::Relaxed);
+		}
+		self.aborted
+	}
+	#[allow(clippy::too_many_arguments)]
+	fn negamax(&mut self, board: &Board, mut depth: u32, ply: u32, mut alpha: i32, beta: i32, previous_pv: &[Move], pv: &mut Vec<Move>) -> i32 {
+		self.nodes += 1;
+		if self.should_abort() {
+			return 0;
+		}
+		if ply > 0 && board.is_insufficient_material() {
+			return 0;
+		}
+		let color = board.side_to_move;
+		let in_check = board.is_in_check(color);
+		// Extend checks so that mates on the horizon are still seen
+		if in_check {
+			depth += 1;
+		}
+		if depth == 0 {
+			return self.quiescence(board, alpha, beta);
+		}
+		let entry = self.tt.probe(board.zobrist(), ply);
+		// The root always searches so that there is a move to return
+		if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+			let cutoff = match entry.bound {
+				Bound::Exact => true,
+				Bound::Lower => entry.score >= beta,
+				Bound::Upper => entry.score <= alpha,
+			};
+			if cutoff {
+				return entry.score;
+			}
+		}
+		let mut moves = board.legal_moves(color);
+		if moves.is_empty() {
+			return if in_check { -MATE_SCORE + ply as i32 } else { 0 };
+		}
+		let hash_move = previous_pv.first().map(TtMove::from).or(entry.and_then(|entry| entry.best_move));
+		order_moves(board, &mut moves, hash_move);
+		let original_alpha = alpha;
+		let mut best_score = -INFINITY;
+		let mut best_move = None;
+		for chess_move in moves {
+			let mut child = board.clone();
+			child.apply_move(&chess_move, color);
+			let mut child_pv = Vec::new();
+			let child_previous = match previous_pv.split_first() {
+				Some((first, rest)) if first.is_same(&chess_move) => rest,
+				_ => &[],
+			};
+			let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, child_previous, &mut child_pv);
+			if self.aborted {
+				return 0;
+			}
+			if score > best_score {
+				best_score = score;
+				best_move = Some(TtMove::from(&chess_move));
+			}
+			if score > alpha {
+				alpha = score;
+				pv.clear();
+				pv.push(chess_move);
+				pv.append(&mut child_pv);
+				if alpha >= beta {
+					break;
+				}
+			}
+		}
+		let bound = if best_score >= beta {
+			Bound::Lower
+		} else if best_score > original_alpha {
+			Bound::Exact
+		} else {
+			Bound::Upper
+		};
+		self.tt.store(board.zobrist(), depth, bound, best_score, best_move, ply);
+		best_score
+	}
+	fn quiescence(&mut self, board: &Board, mut alpha: i32, beta: i32) -> i32 {
+		self.nodes += 1;
+		if self.should_abort() {
+			return 0;
+		}
+		let stand_pat = eval::evaluate(board, self.weights);
+		if stand_pat >= beta {
+			return stand_pat;
+		}
+		alpha = alpha.max(stand_pat);
+		let color = board.side_to_move;
+		let mut captures: Vec<Move> = board.legal_moves(color).into_iter()
+			.filter(|chess_move| is_capture(board, chess_move) || chess_move.move_type == MoveType::Promotion)
+			.collect();
+		order_moves(board, &mut captures, None);
+		for chess_move in captures {
+			let mut child = board.clone();
+			child.apply_move(&chess_move, color);
+			let score = -self.quiescence(&child, -beta, -alpha);
+			if self.aborted {
+				return 0;
+			}
+			if score >= beta {
+				return score;
+			}
+			alpha = alpha.max(score);
+		}
+		alpha
+	}
+}
+
+fn is_capture(board: &Board, chess_move: &Move) -> bool {
+	board.get_square(chess_move.to).piece.is_some()
+		|| (chess_move.piece_type == PieceType::Pawn && board.can_passant == Some(chess_move.to))
+}
+
+// Previous best move first, then captures by most valuable victim and least valuable attacker
+fn order_moves(board: &Board, moves: &mut [Move], best: Option<TtMove>) {
+	moves.sort_by_cached_key(|chess_move| {
+		if best.is_some_and(|best| best.matches(chess_move)) {
+			return i32::MIN;
+		}
+		let victim = match &board.get_square(chess_move.to).piece {
+			Some(piece) => piece_value(piece.piece_type),
+			None if is_capture(board, chess_move) => piece_value(PieceType::Pawn),
+			None => 0,
+		};
+		let promotion = chess_move.promotion.map_or(0, piece_value);
+		if victim == 0 && promotion == 0 {
+			return 0;
+		}
+		-(victim * 10 + promotion - piece_value(chess_move.piece_type) / 10)
+	});
+}
+
+fn piece_value(piece_type: PieceType) -> i32 {
+	match piece_type {
+		PieceType::Pawn => 100,
+		PieceType::Knight => 320,
+		PieceType::Bishop => 330,
+		PieceType::Rook => 500,
+		PieceType::Queen => 900,
+		PieceType::King => 0,
+	}
+}
@@ -1,23 +1,27 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use text_io::read;
-
-    use crate::{board::{Board, BoardPerspective, File, Rank}, game::{Game, Move}, pieces::{Piece, PieceType}};
+    use std::{io::Write, sync::{atomic::AtomicBool, Arc, Mutex}, time::Duration};
 
+    use crate::{board::{Board, BoardPerspective, Color, Coordinate, File, Rank}, clock::{self, Clock, ManualClock, TimeControl, TimingMode}, controller::{Action, EngineController, PlayerController, RandomMover, ScriptedController}, eval::{self, EvalConfigError, EvalWeights, Score}, fen::{FenError, STARTING_FEN}, game::{CastlingRights, Game, GameResult, Move, MoveError, MoveType, Player, Termination}, pgn::{PgnErrorKind, PgnReader}, pieces::{Piece, PieceType}, position::Position, search::{self, SearchLimits}, tree::{Evaluation, GameTree}, tt::{Bound, TranspositionTable, TtMove}, uci, xboard, zobrist};
 
     #[test]
     fn run_game() {
-        print!("Welcome to Chess-rs, please enter desired game time in seconds:");
-        let time: u32 = read!();
-        let mut game = Game::new(time);
-        game.start()
+        let mut game = Game::new(0);
+        let mut engine = EngineController::new(SearchLimits { depth: Some(1), ..Default::default() });
+        game.play(&mut engine, &mut RandomMover::new(7));
+        // The seeded opponent makes the game the same every run
+        assert_eq!((game.result, game.termination), (GameResult::WhiteWins, Some(Termination::Checkmate)));
+        // The recorded game replays to the same position
+        let replayed = Game::from_pgn(&game.to_pgn()).unwrap();
+        assert_eq!(replayed.board.to_fen(), game.board.to_fen());
+        assert_eq!(replayed.result, game.result);
     }
 
     #[test]
     fn init_board() {
         let  board = Board::default();
-        print!("{}", board.to_string(BoardPerspective::Black));
-        print!("\n");
+        println!("{}", board.to_string(BoardPerspective::Black));
         print!("{}", board.to_string(BoardPerspective::White));
     }
 
@@ -106,6 +110,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Four as usize][File::D as usize].piece = Some(Piece::new(player2.color, PieceType::Pawn));
+        board.rehash();
         let _ = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move2, player2);
@@ -127,6 +132,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Five as usize][File::D as usize].piece = Some(Piece::new(player.color, PieceType::Pawn));
+        board.rehash();
         let _ = board.execute_move(&chess_move, player2);
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move2, player);
@@ -144,6 +150,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::B as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -160,6 +167,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Seven as usize][File::B as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -206,6 +214,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::A as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -222,6 +231,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Seven as usize][File::A as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -238,6 +248,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::D as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -245,6 +256,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "moves White's queen for Black, failing since before check rules"]
     fn valid_queen_file_move_black() {
         let mut game = Game::new(3600);
         let mut board = game.board;
@@ -254,6 +266,24 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[1][3].piece = None;
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        print!("{}", board.to_string(player.color.into()));
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+    }
+
+    #[test]
+    fn valid_queen_file_move_black_d8() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.1;
+        let chess_move = match Move::try_from(("Qd8d5", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Seven as usize][File::D as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -270,6 +300,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::C as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -277,6 +308,7 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "moves White's queen for Black, failing since before check rules"]
     fn valid_queen_diag_move_black() {
         let mut game = Game::new(3600);
         let mut board = game.board;
@@ -286,6 +318,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[1][2].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -293,6 +326,24 @@ mod tests {
     }
 
     #[test]
+    fn valid_queen_diag_move_black_d8() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.1;
+        let chess_move = match Move::try_from(("Qd8b6", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Seven as usize][File::C as usize].piece = None;
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        print!("{}", board.to_string(player.color.into()));
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+    }
+
+    #[test]
+    #[ignore = "clears d2 so the king is blocked by its own pawn on e2, failing since before check rules"]
     fn valid_king_file_move() {
         let mut game = Game::new(3600);
         let mut board = game.board;
@@ -302,6 +353,24 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::D as usize].piece = None;
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        print!("{}", board.to_string(player.color.into()));
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+    }
+
+    #[test]
+    fn valid_king_file_move_e2() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.0;
+        let chess_move = match Move::try_from(("Ke1e2", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Two as usize][File::E as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -318,6 +387,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Seven as usize][File::E as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -334,6 +404,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Two as usize][File::D as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -350,6 +421,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         board.squares[Rank::Seven as usize][File::D as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -367,6 +439,7 @@ mod tests {
         };
         board.squares[0][5].piece = None;
         board.squares[0][6].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -385,6 +458,7 @@ mod tests {
         board.squares[0][1].piece = None;
         board.squares[0][2].piece = None;
         board.squares[0][3].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -402,6 +476,7 @@ mod tests {
         };
         board.squares[Rank::Eight as usize][File::F as usize].piece = None;
         board.squares[Rank::Eight as usize][File::G as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -420,6 +495,7 @@ mod tests {
         board.squares[Rank::Eight as usize][File::B as usize].piece = None;
         board.squares[Rank::Eight as usize][File::C as usize].piece = None;
         board.squares[Rank::Eight as usize][File::D as usize].piece = None;
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let move_result = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -440,6 +516,7 @@ mod tests {
             color: player.color(),
             piece_type: PieceType::Pawn,
         });
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let executed_move = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
@@ -460,9 +537,1316 @@ mod tests {
             color: player.color(),
             piece_type: PieceType::Pawn,
         });
+        board.rehash();
         print!("{}", board.to_string(player.color.into()));
         let executed_move = board.execute_move(&chess_move, player);
         print!("{}", board.to_string(player.color.into()));
         assert!(executed_move.is_ok(), "{}", executed_move.unwrap_err().as_str());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pinned_piece() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.0;
+        let chess_move = match Move::try_from(("d2d3", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Four as usize][File::B as usize].piece = Some(Piece::new(Color::Black, PieceType::Bishop));
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        assert!(matches!(move_result, Err(MoveError::Pinned)));
+    }
+
+    #[test]
+    fn king_into_check() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.0;
+        let chess_move = match Move::try_from(("Ke1f2", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Two as usize][File::F as usize].piece = None;
+        board.squares[Rank::Five as usize][File::F as usize].piece = Some(Piece::new(Color::Black, PieceType::Rook));
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        assert!(matches!(move_result, Err(MoveError::InCheck)));
+    }
+
+    #[test]
+    fn castle_through_check() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.0;
+        let chess_move = match Move::try_from(("O-O", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::One as usize][File::F as usize].piece = None;
+        board.squares[Rank::One as usize][File::G as usize].piece = None;
+        board.squares[Rank::Two as usize][File::F as usize].piece = None;
+        board.squares[Rank::Five as usize][File::F as usize].piece = Some(Piece::new(Color::Black, PieceType::Rook));
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        let move_result = board.execute_move(&chess_move, player);
+        assert!(matches!(move_result, Err(MoveError::InCheck)));
+    }
+
+    #[test]
+    fn resolve_check() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let player = &mut game.players.0;
+        let ignore_move = match Move::try_from(("a2a3", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        let block_move = match Move::try_from(("Qd1e2", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        board.squares[Rank::Two as usize][File::E as usize].piece = None;
+        board.squares[Rank::Five as usize][File::E as usize].piece = Some(Piece::new(Color::Black, PieceType::Rook));
+        board.rehash();
+        print!("{}", board.to_string(player.color.into()));
+        assert!(board.is_in_check(player.color));
+        let move_result = board.execute_move(&ignore_move, player);
+        assert!(matches!(move_result, Err(MoveError::InCheck)));
+        let move_result = board.execute_move(&block_move, player);
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+        assert!(!board.is_in_check(player.color));
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let (white, black) = &mut game.players;
+        for (index, notation) in ["f2f3", "e7e5", "g2g4", "Qd8h4"].into_iter().enumerate() {
+            let player = if index % 2 == 0 { &mut *white } else { &mut *black };
+            let chess_move = match Move::try_from((notation, player.color)) {
+                Ok(cm) => cm,
+                Err(err) => panic!("{}", err),
+            };
+            let move_result = board.execute_move(&chess_move, player);
+            assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+        }
+        print!("{}", board.to_string(white.color.into()));
+        assert!(board.is_in_check(white.color));
+        assert!(!board.has_legal_move(white.color));
+        assert!(board.has_legal_move(black.color));
+    }
+
+    #[test]
+    fn stalemate() {
+        let game = Game::new(3600);
+        let mut board = game.board;
+        let black = &game.players.1;
+        board.squares.iter_mut().flatten().for_each(|square| square.piece = None);
+        board.squares[Rank::Eight as usize][File::H as usize].piece = Some(Piece::new(Color::Black, PieceType::King));
+        board.squares[Rank::Seven as usize][File::F as usize].piece = Some(Piece::new(Color::White, PieceType::Queen));
+        board.squares[Rank::One as usize][File::A as usize].piece = Some(Piece::new(Color::White, PieceType::King));
+        board.castling_rights = (CastlingRights::None, CastlingRights::None);
+        board.rehash();
+        print!("{}", board.to_string(black.color.into()));
+        assert!(!board.is_in_check(black.color));
+        assert!(!board.has_legal_move(black.color));
+    }
+
+    #[test]
+    fn legal_moves_start() {
+        let game = Game::new(3600);
+        let board = game.board;
+        assert_eq!(board.legal_moves(game.players.0.color).len(), 20);
+        assert_eq!(board.legal_moves(game.players.1.color).len(), 20);
+    }
+
+    #[test]
+    fn legal_moves_special() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let (white, black) = &mut game.players;
+        board.squares.iter_mut().flatten().for_each(|square| square.piece = None);
+        board.squares[Rank::One as usize][File::E as usize].piece = Some(Piece::new(Color::White, PieceType::King));
+        board.squares[Rank::One as usize][File::A as usize].piece = Some(Piece::new(Color::White, PieceType::Rook));
+        board.squares[Rank::One as usize][File::H as usize].piece = Some(Piece::new(Color::White, PieceType::Rook));
+        board.squares[Rank::Seven as usize][File::B as usize].piece = Some(Piece::new(Color::White, PieceType::Pawn));
+        board.squares[Rank::Five as usize][File::E as usize].piece = Some(Piece::new(Color::White, PieceType::Pawn));
+        board.squares[Rank::Eight as usize][File::G as usize].piece = Some(Piece::new(Color::Black, PieceType::King));
+        board.squares[Rank::Seven as usize][File::D as usize].piece = Some(Piece::new(Color::Black, PieceType::Pawn));
+        board.rehash();
+        let passant_move = match Move::try_from(("d7d5", black.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        let move_result = board.execute_move(&passant_move, black);
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+        print!("{}", board.to_string(white.color.into()));
+        let moves = board.legal_moves(white.color);
+        let notations: Vec<&str> = moves.iter().map(|chess_move| chess_move.notation.as_str()).collect();
+        for expected in ["O-O", "O-O-O", "e5d6", "e5e6", "b7b8=Q", "b7b8=R", "b7b8=B", "b7b8=N"] {
+            assert!(notations.contains(&expected), "missing {}", expected);
+        }
+        assert!(!notations.contains(&"b7b8"));
+        assert!(moves.iter().all(|chess_move| board.clone().execute_move(chess_move, &mut Player::new(Color::White, 0)).is_ok()));
+    }
+
+    #[test]
+    fn perft_start() {
+        let board = Board::default();
+        assert_eq!(board.perft(1), 20);
+        assert_eq!(board.perft(2), 400);
+        assert_eq!(board.perft(3), 8902);
+        assert_eq!(board.perft(4), 197281);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.perft(1), 48);
+        assert_eq!(board.perft(2), 2039);
+        assert_eq!(board.perft(3), 97862);
+    }
+
+    #[test]
+    fn perft_passant() {
+        let board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(board.perft(1), 14);
+        assert_eq!(board.perft(2), 191);
+        assert_eq!(board.perft(3), 2812);
+        assert_eq!(board.perft(4), 43238);
+    }
+
+    #[test]
+    fn perft_promotion() {
+        let board = Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(board.perft(1), 6);
+        assert_eq!(board.perft(2), 264);
+        assert_eq!(board.perft(3), 9467);
+        let board = Board::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(board.perft(1), 44);
+        assert_eq!(board.perft(2), 1486);
+        assert_eq!(board.perft(3), 62379);
+    }
+
+    #[test]
+    fn divide_start() {
+        let board = Board::default();
+        let divided = board.divide(3);
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let knight = divided.iter().find(|(chess_move, _)| chess_move.notation == "Nb1c3").unwrap();
+        assert_eq!(knight.1, 440);
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let board = Board::default();
+        assert_eq!(board.to_fen(), STARTING_FEN);
+        let mut game = Game::new(3600);
+        let mut board = Board::from_fen(STARTING_FEN).unwrap();
+        let player = &mut game.players.0;
+        let chess_move = match Move::try_from(("e2e4", player.color)) {
+            Ok(cm) => cm,
+            Err(err) => panic!("{}", err),
+        };
+        let move_result = board.execute_move(&chess_move, player);
+        assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3pP3/8/8/8/4K3 w Kq d6 12 40",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/K6k b - -").unwrap().to_fen(), "8/8/8/8/8/8/8/K6k b - - 0 1");
+    }
+
+    #[test]
+    fn fen_errors() {
+        assert_eq!(Board::from_fen("8/8/8 w - - 0 1").err(), Some(FenError::RankCount(3)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w -").err(), Some(FenError::FieldCount(3)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/7 w - - 0 1").err(), Some(FenError::RankLength(Rank::One)));
+        assert_eq!(Board::from_fen("9/8/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::Piece('9')));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").err(), Some(FenError::SideToMove("x".to_string())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w KK - 0 1").err(), Some(FenError::CastlingRights("KK".to_string())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - e3 0 1").err(), Some(FenError::EnPassant("e3".to_string())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - - x 1").err(), Some(FenError::HalfmoveClock("x".to_string())));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 0").err(), Some(FenError::FullmoveNumber("0".to_string())));
+    }
+
+    #[test]
+    fn san_moves() {
+        let mut game = Game::new(3600);
+        let mut board = game.board;
+        let (white, black) = &mut game.players;
+        for (index, san) in ["e4", "d5", "exd5", "Nf6", "Nf3", "Nxd5"].into_iter().enumerate() {
+            let player = if index % 2 == 0 { &mut *white } else { &mut *black };
+            let chess_move = match Move::try_from((san, &board)) {
+                Ok(cm) => cm,
+                Err(err) => panic!("{}: {}", san, err.as_str()),
+            };
+            let move_result = board.execute_move(&chess_move, player);
+            assert!(move_result.is_ok(), "{}", move_result.unwrap_err().as_str());
+        }
+        assert_eq!(board.to_fen(), "rnbqkb1r/ppp1pppp/8/3n4/8/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 4");
+        assert_eq!(Move::try_from(("e5", &board)).err(), Some(MoveError::Invalid));
+        assert_eq!(Move::try_from(("Zf3", &board)).err(), Some(MoveError::Notation));
+        assert_eq!(Move::try_from(("N", &board)).err(), Some(MoveError::Notation));
+        assert!(Move::try_from(("e2", Color::White)).is_err());
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        let board = Board::from_fen("k7/8/8/8/4R3/8/8/R4RK1 w - - 0 1").unwrap();
+        assert_eq!(Move::try_from(("Rd1", &board)).err(), Some(MoveError::Ambiguous));
+        assert_eq!(Move::try_from(("Rad1", &board)).unwrap().from.file, File::A);
+        assert_eq!(Move::try_from(("Rfd1", &board)).unwrap().from.file, File::F);
+        let board = Board::from_fen("k7/8/8/8/4R3/8/8/4R1K1 w - - 0 1").unwrap();
+        assert_eq!(Move::try_from(("Re2", &board)).err(), Some(MoveError::Ambiguous));
+        assert_eq!(Move::try_from(("R1e2", &board)).unwrap().from.rank, Rank::One);
+        assert_eq!(Move::try_from(("R4e2", &board)).unwrap().from.rank, Rank::Four);
+        assert_eq!(Move::try_from(("Re4e2", &board)).unwrap().from.rank, Rank::Four);
+    }
+
+    #[test]
+    fn san_promotion_and_castling() {
+        let board = Board::from_fen("k7/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let promotion = Move::try_from(("e8=Q+", &board)).unwrap();
+        assert!(promotion.move_type == MoveType::Promotion && promotion.promotion == Some(PieceType::Queen));
+        assert_eq!(Move::try_from(("e8N", &board)).unwrap().promotion, Some(PieceType::Knight));
+        assert_eq!(Move::try_from(("e8", &board)).err(), Some(MoveError::Invalid));
+        assert!(Move::try_from(("O-O#", &board)).unwrap().move_type == MoveType::CastleKingSide);
+        assert_eq!(Move::try_from(("O-O-O", &board)).err(), Some(MoveError::Invalid));
+    }
+
+    #[test]
+    fn san_generation() {
+        let board = Board::from_fen("7k/8/8/8/4R3/8/8/R4RK1 w - - 0 1").unwrap();
+        let sans: Vec<String> = board.legal_moves(Color::White).iter().map(|chess_move| board.to_san(chess_move)).collect();
+        for expected in ["Rad1", "Rfd1", "Ree1", "Ref4", "Re8+", "Ra8+", "Rh4+"] {
+            assert!(sans.iter().any(|san| san == expected), "missing {} in {:?}", expected, sans);
+        }
+        let board = Board::from_fen("7k/8/8/8/4R3/8/8/R3R1K1 w - - 0 1").unwrap();
+        let sans: Vec<String> = board.legal_moves(Color::White).iter().map(|chess_move| board.to_san(chess_move)).collect();
+        for expected in ["R1e2", "R4e2", "Rad1", "Red1", "Rea4", "Rd4"] {
+            assert!(sans.iter().any(|san| san == expected), "missing {} in {:?}", expected, sans);
+        }
+        let board = Board::from_fen("k7/4P3/1K6/3pP3/8/8/8/7R w - d6 0 1").unwrap();
+        let sans: Vec<String> = board.legal_moves(Color::White).iter().map(|chess_move| board.to_san(chess_move)).collect();
+        for expected in ["e8=Q#", "e8=N", "exd6", "Rh8#", "Rh7"] {
+            assert!(sans.iter().any(|san| san == expected), "missing {} in {:?}", expected, sans);
+        }
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let castle = Move::try_from(("O-O", &board)).unwrap();
+        assert_eq!(board.to_san(&castle), "O-O");
+    }
+
+    #[test]
+    fn san_round_trip() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for chess_move in board.legal_moves(board.side_to_move) {
+            let san = board.to_san(&chess_move);
+            let parsed = match Move::try_from((san.as_str(), &board)) {
+                Ok(cm) => cm,
+                Err(err) => panic!("{}: {}", san, err.as_str()),
+            };
+            assert!(parsed.from == chess_move.from && parsed.to == chess_move.to && parsed.promotion == chess_move.promotion, "{}", san);
+        }
+    }
+
+    fn play_sans(game: &mut Game, sans: &[&str]) {
+        for san in sans {
+            let result = Move::try_from((*san, &game.board)).and_then(|chess_move| game.play_move(chess_move));
+            assert!(result.is_ok(), "{}: {}", san, result.unwrap_err().as_str());
+        }
+    }
+
+    #[test]
+    fn pgn_export() {
+        let mut game = Game::new(300);
+        game.set_tag("Event", "Club \"Blitz\"");
+        game.set_tag("White", "Fool");
+        play_sans(&mut game, &["f3", "e5", "g4", "Qh4"]);
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Checkmate)));
+        assert_eq!(game.to_pgn(), concat!(
+            "[Event \"Club \\\"Blitz\\\"\"]\n",
+            "[Site \"?\"]\n",
+            "[Date \"????.??.??\"]\n",
+            "[Round \"?\"]\n",
+            "[White \"Fool\"]\n",
+            "[Black \"?\"]\n",
+            "[Result \"0-1\"]\n",
+            "[TimeControl \"300\"]\n",
+            "[Termination \"normal\"]\n",
+            "\n",
+            "1. f3 e5 2. g4 Qh4# 0-1\n",
+        ));
+    }
+
+    #[test]
+    fn pgn_export_setup() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 5";
+        let mut game = Game::from_fen(fen, 60).unwrap();
+        play_sans(&mut game, &["Nc6", "Nf3"]);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n"));
+        assert!(pgn.contains(&format!("[FEN \"{}\"]\n", fen)));
+        assert!(pgn.ends_with("\n5... Nc6 6. Nf3 *\n"), "{}", pgn);
+    }
+
+    #[test]
+    fn pgn_export_wrapping() {
+        let mut game = Game::new(60);
+        // Pawn pushes never repeat a position, so the game stays ongoing however long the movetext
+        for rank in ['3', '4'] {
+            for file in "abcdefgh".chars() {
+                let black_rank = if rank == '3' { '6' } else { '5' };
+                play_sans(&mut game, &[&format!("{}{}", file, rank), &format!("{}{}", file, black_rank)]);
+            }
+        }
+        play_sans(&mut game, &["Nc3", "Nc6", "Nf3", "Nf6"]);
+        let pgn = game.to_pgn();
+        let movetext: Vec<&str> = pgn.split("\n\n").nth(1).unwrap().lines().collect();
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= 79));
+        assert!(movetext.first().unwrap().starts_with("1. a3 a6 2. b3 b6"));
+        assert!(movetext.last().unwrap().ends_with("17. Nc3 Nc6 18. Nf3 Nf6 *"), "{}", pgn);
+    }
+
+    const PGN_GAMES: &str = r#"[Event "Casual"]
+[Site "Club"]
+[Date "2024.01.02"]
+[Round "1"]
+[White "Anna \"A\" B"]
+[Black "Ben"]
+[Result "1-0"]
+
+1. e4 {King's pawn} e5 2. Nf3 $1 Nc6 (2... d6 3. d4 (3. Bc4) exd4) 3. Bb5 a6
+; a comment to the end of the line
+4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event "Casual"]
+[Result "0-1"]
+[SetUp "1"]
+[FEN "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2"]
+
+2...Qh4# {
+mate in one
+} 0-1
+"#;
+
+    #[test]
+    fn pgn_import() {
+        let games: Vec<Game> = PgnReader::new(PGN_GAMES.as_bytes()).map(|game| game.unwrap()).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("Anna \"A\" B"));
+        assert_eq!((games[0].result, games[0].termination), (GameResult::WhiteWins, None));
+        assert_eq!(games[0].turns.len(), 5);
+        assert_eq!(games[0].turns_to_string(), "1. e4 e5; 2. Nf3 Nc6; 3. Bb5 a6; 4. Ba4 Nf6; 5. O-O Be7");
+        assert_eq!((games[1].result, games[1].termination), (GameResult::BlackWins, Some(Termination::Checkmate)));
+        assert!(games[1].turns[0].1.as_ref().unwrap().is_mate);
+        let exported = games[0].to_pgn();
+        let reimported = Game::from_pgn(&exported).unwrap();
+        assert_eq!(reimported.to_pgn(), exported);
+        assert_eq!(reimported.board.to_fen(), games[0].board.to_fen());
+    }
+
+    #[test]
+    fn pgn_import_errors() {
+        let err = Game::from_pgn("[Event \"?\"]\n\n1. e4 e5\n2. Ke3 Nc6 *\n").err().unwrap();
+        assert_eq!((err.line, err.column), (4, 4));
+        assert_eq!(err.kind, PgnErrorKind::Move("Ke3".to_string(), MoveError::Invalid));
+        let err = Game::from_pgn("[Event \"?\"\n\n1. e4 *\n").err().unwrap();
+        assert_eq!((err.line, err.column, err.kind), (1, 1, PgnErrorKind::Tag));
+        let err = Game::from_pgn("1. e4 { unfinished\n").err().unwrap();
+        assert_eq!((err.line, err.column, err.kind), (1, 7, PgnErrorKind::UnterminatedComment));
+        let err = Game::from_pgn("1. e4 e5 ) *\n").err().unwrap();
+        assert_eq!((err.line, err.column, err.kind), (1, 10, PgnErrorKind::UnbalancedVariation));
+    }
+
+    #[test]
+    fn pgn_import_stream() {
+        let archive = PGN_GAMES.repeat(200);
+        let mut count = 0;
+        for game in PgnReader::new(archive.as_bytes()) {
+            assert!(game.unwrap().result.winner().is_some());
+            count += 1;
+        }
+        assert_eq!(count, 400);
+    }
+
+    #[test]
+    fn uci_moves() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for chess_move in board.legal_moves(board.side_to_move) {
+            let uci = chess_move.to_uci();
+            let parsed = match Move::from_uci(&uci, &board) {
+                Ok(cm) => cm,
+                Err(err) => panic!("{}: {}", uci, err.as_str()),
+            };
+            assert!(parsed.move_type == chess_move.move_type && parsed.piece_type == chess_move.piece_type, "{}", uci);
+            assert_eq!(parsed.to_uci(), uci);
+        }
+        assert!(Move::from_uci("e1g1", &board).unwrap().move_type == MoveType::CastleKingSide);
+        assert!(Move::from_uci("e1c1", &board).unwrap().move_type == MoveType::CastleQueenSide);
+        assert_eq!(Move::from_uci("e1e3", &board).err(), Some(MoveError::Invalid));
+        assert_eq!(Move::from_uci("e1", &board).err(), Some(MoveError::Notation));
+        assert_eq!(Move::from_uci("e1g1x", &board).err(), Some(MoveError::Notation));
+        let board = Board::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = Move::from_uci("e7e8n", &board).unwrap();
+        assert_eq!(promotion.promotion, Some(PieceType::Knight));
+        assert_eq!(promotion.to_uci(), "e7e8n");
+        assert_eq!(Move::from_uci("e7e8", &board).err(), Some(MoveError::Invalid));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(str::to_string).collect()
+        }
+    }
+
+    #[test]
+    fn uci_session() {
+        let output = SharedOutput::default();
+        let session = "uci\nsetoption name Hash value 1\nisready\nucinewgame\nposition startpos moves e2e4 e7e5 g1f3\ngo depth 2\nquit\n";
+        uci::run(session.as_bytes(), output.clone());
+        let lines = output.lines();
+        assert!(lines[0].starts_with("id name chess_rs"));
+        assert!(lines.contains(&"uciok".to_string()));
+        assert!(lines.contains(&"readyok".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("option name Hash type spin")));
+        let best_move = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+        let mut game = Game::new(0);
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            game.play_move(Move::from_uci(uci, &game.board).unwrap()).unwrap();
+        }
+        assert!(Move::from_uci(best_move, &game.board).is_ok(), "{}", best_move);
+    }
+
+    #[test]
+    fn uci_position_past_game_end() {
+        let output = SharedOutput::default();
+        let session = "position fen 7k/8/8/8/8/8/8/K6R w - - 149 80 moves h1h2 h8g8 a1b1\ngo depth 1\nposition startpos moves e2e5\nquit\n";
+        uci::run(session.as_bytes(), output.clone());
+        let lines = output.lines();
+        let best_move = lines.iter().find_map(|line| line.strip_prefix("bestmove ")).unwrap();
+        let mut board = Board::from_fen("7k/8/8/8/8/8/8/K6R w - - 149 80").unwrap();
+        for uci in ["h1h2", "h8g8", "a1b1"] {
+            let color = board.side_to_move;
+            board.execute_move(&Move::from_uci(uci, &board).unwrap(), &mut Player::new(color, 0)).unwrap();
+        }
+        let reply = Move::from_uci(best_move, &board).unwrap();
+        assert!(board.legal_moves(Color::Black).iter().any(|chess_move| chess_move.is_same(&reply)), "{}", best_move);
+        assert_eq!(lines.last().unwrap(), "info string e2e5 Move is invalid!");
+    }
+
+    #[test]
+    fn uci_infinite() {
+        let output = SharedOutput::default();
+        let (sender, receiver) = std::sync::mpsc::channel::<String>();
+        let session_output = output.clone();
+        let session = std::thread::spawn(move || {
+            let input = std::io::BufReader::new(ChannelReader(receiver, Vec::new()));
+            uci::run(input, session_output);
+        });
+        sender.send("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 moves e2e4\n".to_string()).unwrap();
+        sender.send("go infinite\n".to_string()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!output.lines().iter().any(|line| line.starts_with("bestmove")));
+        sender.send("stop\n".to_string()).unwrap();
+        sender.send("quit\n".to_string()).unwrap();
+        session.join().unwrap();
+        let lines = output.lines();
+        assert_eq!(lines.iter().filter(|line| line.starts_with("bestmove")).count(), 1);
+        let board = Board::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert!(Move::from_uci(lines.last().unwrap().strip_prefix("bestmove ").unwrap(), &board).is_ok());
+    }
+
+    struct ChannelReader(std::sync::mpsc::Receiver<String>, Vec<u8>);
+
+    impl std::io::Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.1.is_empty() {
+                match self.0.recv() {
+                    Ok(line) => self.1 = line.into_bytes(),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let count = buf.len().min(self.1.len());
+            buf[..count].copy_from_slice(&self.1[..count]);
+            self.1.drain(..count);
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn xboard_session() {
+        let output = SharedOutput::default();
+        let session = "xboard\nprotover 2\nnew\nsd 2\ntime 30000\notim 30000\nusermove e2e4\nusermove e2e5\nping 7\nquit\n";
+        xboard::run(session.as_bytes(), output.clone());
+        let lines = output.lines();
+        assert!(lines[0].starts_with("feature ") && lines[0].contains("setboard=1") && lines[0].ends_with("done=1"));
+        let reply = lines[1].strip_prefix("move ").unwrap();
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert!(Move::from_uci(reply, &board).is_ok(), "{}", reply);
+        assert_eq!(lines[2], "Illegal move: e2e5");
+        assert_eq!(lines[3], "pong 7");
+    }
+
+    #[test]
+    fn xboard_setboard_undo() {
+        let output = SharedOutput::default();
+        let session = concat!(
+            "xboard\nprotover 2\nforce\n",
+            "setboard 7k/8/6K1/8/8/8/8/R7 w - - 0 1\n",
+            "usermove a1a7\nundo\nusermove a1a8\n",
+            "setboard not a position\nquit\n",
+        );
+        xboard::run(session.as_bytes(), output.clone());
+        let lines = output.lines();
+        assert_eq!(lines[1..], ["1-0 {White mates}", "tellusererror Illegal position"]);
+    }
+
+    #[test]
+    fn xboard_draw_claims() {
+        // The fifty-move draw is claimed by the side that is behind but not by the side that is ahead
+        let play = |fen: &str| {
+            let output = SharedOutput::default();
+            xboard::run(format!("xboard\nprotover 2\nforce\nsetboard {}\nsd 2\ngo\nquit\n", fen).as_bytes(), output.clone());
+            output.lines()
+        };
+        let lines = play("k7/8/8/8/8/8/5q2/K7 w - - 99 80");
+        assert_eq!(lines[1..], ["move a1b1", "1/2-1/2 {Draw by the fifty-move rule}"]);
+        let lines = play("k7/8/8/8/8/8/5Q2/K7 w - - 99 80");
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+    }
+
+    #[test]
+    fn search_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search::search(&board, SearchLimits { depth: Some(3), ..Default::default() }, &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {});
+        assert_eq!(result.best_move().unwrap().to_uci(), "a1a8");
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn search_mate_in_two() {
+        let board = Board::from_fen("1r4k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        let mut depths = Vec::new();
+        let result = search::search(&board, SearchLimits { depth: Some(4), ..Default::default() }, &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(false), |info| depths.push(info.depth));
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(depths, (1..=result.depth).collect::<Vec<_>>());
+        // The principal variation is a legal line ending in mate
+        let mut board = board;
+        for chess_move in &result.pv {
+            assert!(board.legal_moves(board.side_to_move).iter().any(|legal| legal.to_uci() == chess_move.to_uci()));
+            board.apply_move(chess_move, board.side_to_move);
+        }
+        assert!(board.is_in_check(board.side_to_move) && !board.has_legal_move(board.side_to_move));
+    }
+
+    #[test]
+    fn search_limits() {
+        let board = Board::default();
+        let result = search::search(&board, SearchLimits { depth: Some(2), ..Default::default() }, &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {});
+        assert_eq!(result.depth, 2);
+        assert_eq!(result.pv.len(), 2);
+        let result = search::search(&board, SearchLimits { nodes: Some(500), ..Default::default() }, &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {});
+        assert!(result.nodes <= 500);
+        assert!(result.best_move().is_some());
+        let start = std::time::Instant::now();
+        let limits = SearchLimits { time: Some(std::time::Duration::from_millis(100)), ..Default::default() };
+        let result = search::search(&board, limits, &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {});
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert!(result.best_move().is_some());
+        let result = search::search(&board, SearchLimits::default(), &EvalWeights::DEFAULT, &mut TranspositionTable::new(1), &AtomicBool::new(true), |_| {});
+        assert_eq!(result.depth, 0);
+        assert!(result.best_move().is_some());
+    }
+
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let ranks: Vec<String> = fields[0].split('/').rev().map(|rank| rank.chars().map(|char| {
+            if char.is_ascii_uppercase() { char.to_ascii_lowercase() } else { char.to_ascii_uppercase() }
+        }).collect()).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} - - 0 1", ranks.join("/"), side)
+    }
+
+    #[test]
+    fn eval_symmetry() {
+        let weights = EvalWeights::default();
+        assert_eq!(eval::trace(&Board::default(), &weights).total, 0);
+        assert_eq!(eval::trace(&Board::default(), &weights).phase, 24);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R b - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(eval::trace(&board, &weights).total, -eval::trace(&mirrored, &weights).total, "{}", fen);
+            assert_eq!(eval::evaluate(&board, &weights), eval::evaluate(&mirrored, &weights), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn eval_terms() {
+        let weights = EvalWeights::default();
+        // Two isolated, doubled and passed white pawns against a lone king
+        let board = Board::from_fen("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1").unwrap();
+        let trace = eval::trace(&board, &weights);
+        assert_eq!(trace.phase, 0);
+        let (_, white, black) = trace.terms.iter().find(|(name, _, _)| *name == "Pawn structure").unwrap();
+        assert_eq!(*white, weights.doubled_pawn + weights.isolated_pawn * 2 + weights.passed_pawn[1] + weights.passed_pawn[2]);
+        assert_eq!(*black, Score::default());
+        let (_, white, _) = trace.terms.iter().find(|(name, _, _)| *name == "Material").unwrap();
+        assert_eq!(*white, weights.material[PieceType::Pawn as usize] * 2);
+        assert_eq!(trace.total, trace.terms.iter().map(|(_, white, black)| (*white - *black).1).sum::<i32>());
+        // A blocked pawn is not passed, and a castled king with its pawns is safer than an exposed one
+        let blocked = Board::from_fen("4k3/p7/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+        let (_, white, _) = eval::trace(&blocked, &weights).terms.into_iter().find(|(name, _, _)| *name == "Pawn structure").unwrap();
+        assert_eq!(white, weights.isolated_pawn);
+        let sheltered = Board::from_fen("r5k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let exposed = Board::from_fen("r5k1/5ppp/8/8/8/5PPP/8/R2K4 w - - 0 1").unwrap();
+        let king_safety = |board: &Board| eval::trace(board, &weights).terms.into_iter().find(|(name, _, _)| *name == "King safety").unwrap().1;
+        assert!(king_safety(&sheltered).0 > king_safety(&exposed).0);
+        // Trace output lists every term
+        let output = eval::trace(&sheltered, &weights).to_string();
+        for name in ["Material", "Piece-square", "Pawn structure", "King safety", "Mobility", "Phase"] {
+            assert!(output.contains(name), "{}", output);
+        }
+    }
+
+    #[test]
+    fn eval_config() {
+        let weights = EvalWeights::default();
+        assert_eq!(EvalWeights::from_config(&weights.to_config()).unwrap(), weights);
+        let config = "# Heavier pawns\nmaterial.pawn = 110/130\ndoubled_pawn = -25 # both phases\n\npassed_pawn = 0 1 2 3 4 5 6/7 0\n";
+        let loaded = EvalWeights::from_config(config).unwrap();
+        assert_eq!(loaded.material[PieceType::Pawn as usize], Score(110, 130));
+        assert_eq!(loaded.doubled_pawn, Score(-25, -25));
+        assert_eq!(loaded.passed_pawn[6], Score(6, 7));
+        assert_eq!(loaded.mobility, weights.mobility);
+        assert_eq!(EvalWeights::from_config("tempo = 10"), Err(EvalConfigError::UnknownTerm(1, "tempo".to_string())));
+        assert_eq!(EvalWeights::from_config("\ndoubled_pawn = 1 2"), Err(EvalConfigError::Count(2, 1, 2)));
+        assert_eq!(EvalWeights::from_config("doubled_pawn = x"), Err(EvalConfigError::Value(1, "doubled_pawn".to_string())));
+        assert_eq!(EvalWeights::from_config("doubled_pawn"), Err(EvalConfigError::Syntax(1)));
+        assert!(matches!(EvalWeights::load("does/not/exist.cfg"), Err(EvalConfigError::Io(_))));
+        let output = SharedOutput::default();
+        uci::run("setoption name EvalFile value does/not/exist.cfg\neval\nquit\n".as_bytes(), output.clone());
+        let lines = output.lines();
+        assert!(lines[0].starts_with("info string "));
+        assert!(lines.iter().any(|line| line.starts_with("Material")));
+    }
+
+    // Hands every legal move within the given depth to `check`, with the board it is made from
+    fn walk_moves(board: &Board, depth: u32, check: &impl Fn(&Board, &Move)) {
+        for chess_move in board.legal_moves(board.side_to_move) {
+            check(board, &chess_move);
+            if depth > 1 {
+                let mut child = board.clone();
+                child.apply_move(&chess_move, board.side_to_move);
+                walk_moves(&child, depth - 1, check);
+            }
+        }
+    }
+
+    #[test]
+    fn zobrist_incremental() {
+        // The incrementally updated hash matches a fresh one after every move from these
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            walk_moves(&Board::from_fen(fen).unwrap(), 2, &|board, chess_move| {
+                let mut child = board.clone();
+                child.apply_move(chess_move, board.side_to_move);
+                assert_eq!(child.zobrist(), zobrist::hash(&child), "{}", child.to_fen());
+            });
+        }
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["e4", "d5", "exd5", "c5", "dxc6", "Nf6", "cxb7", "e5", "bxa8=Q", "Bc5", "Nf3", "O-O"]);
+        assert_eq!(game.board.zobrist(), zobrist::hash(&game.board));
+        // Editing squares directly needs a rehash to bring the hash back in step
+        game.board.squares[Rank::One as usize][File::A as usize].piece = None;
+        assert_ne!(game.board.zobrist(), zobrist::hash(&game.board));
+        game.board.rehash();
+        assert_eq!(game.board.zobrist(), zobrist::hash(&game.board));
+    }
+
+    #[test]
+    fn zobrist_positions() {
+        let start = Board::default().zobrist();
+        assert_eq!(start, Board::from_fen(STARTING_FEN).unwrap().zobrist());
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert_eq!(game.board.zobrist(), start);
+        let mut other = Game::new(0);
+        play_sans(&mut other, &["Nc3", "Nf6", "Nf3", "Nc6"]);
+        let mut transposed = Game::new(0);
+        play_sans(&mut transposed, &["Nf3", "Nc6", "Nc3", "Nf6"]);
+        assert_eq!(other.board.zobrist(), transposed.board.zobrist());
+        let hash = |fen: &str| Board::from_fen(fen).unwrap().zobrist();
+        assert_ne!(hash("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), hash("4k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+        assert_ne!(hash("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), hash("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1"));
+        // The en passant square only matters when the capture is possible
+        assert_eq!(hash("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1"));
+        assert_ne!(hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1"));
+        // Move counters are not part of the position
+        assert_eq!(hash("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), hash("4k3/8/8/8/8/8/8/4K3 w - - 12 40"));
+    }
+
+    #[test]
+    fn tt_store_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let chess_move = Move::new(PieceType::Pawn, ('e', '2').into(), ('e', '4').into(), MoveType::Normal, None);
+        tt.store(42, 5, Bound::Lower, 120, Some(TtMove::from(&chess_move)), 2);
+        let entry = tt.probe(42, 2).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score), (5, Bound::Lower, 120));
+        assert!(entry.best_move.unwrap().matches(&chess_move));
+        assert!(tt.probe(43, 2).is_none());
+        // A colliding shallower entry from the same search does not evict a deeper one
+        let colliding = 42 + tt.len() as u64;
+        tt.store(colliding, 2, Bound::Upper, 0, None, 0);
+        assert!(tt.probe(colliding, 0).is_none());
+        tt.new_search();
+        tt.store(colliding, 2, Bound::Upper, 0, None, 0);
+        assert!(tt.probe(42, 0).is_none());
+        assert_eq!(tt.probe(colliding, 0).unwrap().bound, Bound::Upper);
+        // Mate found three plies below a node stored at ply 4 is mate in seven from a root at ply 0
+        tt.store(7, 3, Bound::Exact, search::MATE_SCORE - 7, None, 4);
+        assert_eq!(tt.probe(7, 0).unwrap().score, search::MATE_SCORE - 3);
+        tt.store(8, 3, Bound::Exact, -search::MATE_SCORE + 6, None, 4);
+        assert_eq!(tt.probe(8, 1).unwrap().score, -search::MATE_SCORE + 3);
+        tt.clear();
+        assert!(tt.probe(7, 0).is_none());
+        let mut disabled = TranspositionTable::new(0);
+        assert!(disabled.is_empty());
+        disabled.store(42, 5, Bound::Exact, 0, None, 0);
+        assert!(disabled.probe(42, 0).is_none());
+    }
+
+    #[test]
+    fn tt_node_reduction() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let limits = SearchLimits { depth: Some(3), ..Default::default() };
+        let search = |tt: &mut TranspositionTable| search::search(&board, limits, &EvalWeights::DEFAULT, tt, &AtomicBool::new(false), |_| {});
+        let without = search(&mut TranspositionTable::new(0));
+        let mut tt = TranspositionTable::new(4);
+        let with = search(&mut tt);
+        assert!(with.nodes < without.nodes, "{} >= {}", with.nodes, without.nodes);
+        // A second search of the same position starts from what the first one stored
+        let again = search(&mut tt);
+        assert!(again.nodes < with.nodes, "{} >= {}", again.nodes, with.nodes);
+        assert_eq!(again.depth, 3);
+    }
+
+    #[test]
+    fn repetition_draws() {
+        let mut game = Game::new(0);
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        play_sans(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(game.claimable_draw(), None);
+        play_sans(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.result, GameResult::Ongoing);
+        assert_eq!(game.claimable_draw(), Some(Termination::ThreefoldRepetition));
+        play_sans(&mut game, &shuffle);
+        play_sans(&mut game, &shuffle[..3]);
+        assert_eq!(game.result, GameResult::Ongoing);
+        play_sans(&mut game, &shuffle[3..]);
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::FivefoldRepetition)));
+        assert_eq!(game.result_token(), "1/2-1/2");
+        // Lost castling rights make an otherwise identical position a new one
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["e4", "e5", "Ke2", "Ke7", "Ke1", "Ke8"]);
+        assert_eq!(game.repetition_count(), 1);
+        play_sans(&mut game, &["Ke2", "Ke7", "Ke1", "Ke8", "Ke2", "Ke7", "Ke1", "Ke8"]);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.claim_draw(), Some(Termination::ThreefoldRepetition));
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::ThreefoldRepetition)));
+    }
+
+    #[test]
+    fn move_count_draws() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 98 80", 0).unwrap();
+        play_sans(&mut game, &["Ra2"]);
+        assert_eq!(game.claim_draw(), None);
+        play_sans(&mut game, &["Kd8"]);
+        assert_eq!(game.claimable_draw(), Some(Termination::FiftyMove));
+        // A pawn move resets the count
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80", 0).unwrap();
+        play_sans(&mut game, &["e3"]);
+        assert_eq!(game.claimable_draw(), None);
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 80", 0).unwrap();
+        play_sans(&mut game, &["Ra2"]);
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::SeventyFiveMove)));
+        // Checkmate on the last move still counts
+        let mut game = Game::from_fen("4k3/R7/4K3/8/8/8/8/8 w - - 149 80", 0).unwrap();
+        play_sans(&mut game, &["Ra8#"]);
+        assert_eq!((game.result, game.termination), (GameResult::WhiteWins, Some(Termination::Checkmate)));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let dead = |fen: &str| Board::from_fen(fen).unwrap().is_insufficient_material();
+        assert!(dead("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(dead("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(dead("4k3/8/8/8/8/8/8/1n2K3 w - - 0 1"));
+        // Bishops on c1 and f8 are both on dark squares
+        assert!(dead("5b2/4k3/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!dead("2b5/4k3/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/8/1n1BK3 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        assert!(!dead(STARTING_FEN));
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3n4/4K2b w - - 0 1", 0).unwrap();
+        play_sans(&mut game, &["Kxd2"]);
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::InsufficientMaterial)));
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3r4/2B1K3 w - - 0 1", 0).unwrap();
+        play_sans(&mut game, &["Bxd2"]);
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::InsufficientMaterial)));
+        assert_eq!(game.result_token(), "1/2-1/2");
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1", 0).unwrap();
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::InsufficientMaterial)));
+    }
+
+    #[test]
+    fn game_result() {
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["e4", "e5"]);
+        assert!(!game.is_over());
+        game.resign(Color::White);
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Resignation)));
+        // Whatever happens afterwards does not change how the game ended
+        game.agree_draw();
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Resignation)));
+        assert!(game.to_pgn().contains("[Termination \"normal\"]\n"));
+        let mut game = Game::new(0);
+        game.end(GameResult::WhiteWins, Termination::Timeout);
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Result \"1-0\"]\n[TimeControl \"-\"]\n[Termination \"time forfeit\"]\n"), "{}", pgn);
+        let imported = Game::from_pgn(&pgn).unwrap();
+        assert_eq!((imported.result, imported.termination), (GameResult::WhiteWins, Some(Termination::Timeout)));
+        assert_eq!(imported.to_pgn(), pgn);
+        let mut game = Game::new(0);
+        game.abandon(Color::Black);
+        assert_eq!(game.result.winner(), Some(Color::White));
+        assert_eq!(game.termination.unwrap().pgn_tag(), "abandoned");
+        // A drawn result on a repeated position is read as the claim it was
+        let imported = Game::from_pgn("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 1/2-1/2\n").unwrap();
+        assert_eq!((imported.result, imported.termination), (GameResult::Draw, Some(Termination::ThreefoldRepetition)));
+        let imported = Game::from_pgn("1. e4 e5 1/2-1/2\n").unwrap();
+        assert_eq!(imported.termination, Some(Termination::Agreement));
+        assert_eq!(GameResult::Ongoing.as_str(), "*");
+    }
+
+    fn clocked_game(fen: &str, control: &str) -> (Game, ManualClock) {
+        let mut game = Game::from_fen(fen, 0).unwrap();
+        let source = ManualClock::default();
+        game.set_clock(Clock::new(TimeControl::try_from(control).unwrap(), source.clone()));
+        (game, source)
+    }
+
+    #[test]
+    fn time_control_parse() {
+        for control in ["300", "300+2", "40/5400+30:1800+30", "60d5", "60b5", "40/7200:20/3600:900+30"] {
+            assert_eq!(TimeControl::try_from(control).unwrap().to_string(), control);
+        }
+        let control = TimeControl::try_from("40/5400+30:1800+30").unwrap();
+        assert_eq!(control.stages.len(), 2);
+        assert_eq!(control.stages[0].moves, Some(40));
+        assert_eq!(control.stages[1].base, Duration::from_secs(1800));
+        assert_eq!(control.stages[1].mode, TimingMode::Fischer);
+        assert_eq!(TimeControl::try_from("60d5").unwrap().stages[0].mode, TimingMode::SimpleDelay);
+        assert_eq!(TimeControl::try_from("60b5").unwrap().stages[0].mode, TimingMode::Bronstein);
+        for invalid in ["", "abc", "40/", "0/300", "300+x", "300:"] {
+            assert!(TimeControl::try_from(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(clock::format_duration(Duration::from_secs(5400)), "1:30:00");
+        assert_eq!(clock::format_duration(Duration::from_secs(65)), "1:05");
+    }
+
+    #[test]
+    fn clock_modes() {
+        let seconds = Duration::from_secs;
+        let (mut game, source) = clocked_game(STARTING_FEN, "60+2");
+        assert_eq!(game.tag("TimeControl"), Some("60+2"));
+        source.advance(seconds(5));
+        assert_eq!(game.clock.as_ref().unwrap().remaining(&game.players.0), seconds(55));
+        play_sans(&mut game, &["e4"]);
+        assert_eq!(game.players.0.time, seconds(57));
+        source.advance(seconds(1));
+        play_sans(&mut game, &["e5"]);
+        assert_eq!(game.players.1.time, seconds(61));
+        // A simple delay passes before the clock runs
+        let (mut game, source) = clocked_game(STARTING_FEN, "60d5");
+        source.advance(seconds(3));
+        play_sans(&mut game, &["e4"]);
+        assert_eq!(game.players.0.time, seconds(60));
+        source.advance(seconds(8));
+        assert_eq!(game.clock.as_ref().unwrap().remaining(&game.players.1), seconds(57));
+        play_sans(&mut game, &["e5"]);
+        assert_eq!(game.players.1.time, seconds(57));
+        // Bronstein gives back what was used, up to the delay
+        let (mut game, source) = clocked_game(STARTING_FEN, "60b5");
+        source.advance(seconds(3));
+        play_sans(&mut game, &["e4"]);
+        assert_eq!(game.players.0.time, seconds(60));
+        source.advance(seconds(8));
+        play_sans(&mut game, &["e5"]);
+        assert_eq!(game.players.1.time, seconds(57));
+    }
+
+    #[test]
+    fn clock_stages() {
+        let seconds = Duration::from_secs;
+        let (mut game, source) = clocked_game(STARTING_FEN, "2/60:30+5");
+        for san in ["Nf3", "Nf6", "Nc3", "Nc6"] {
+            source.advance(seconds(10));
+            play_sans(&mut game, &[san]);
+        }
+        // Two moves in the first stage, then the second stage's time arrives
+        assert_eq!(game.players.0.time, seconds(60 - 20 + 30));
+        source.advance(seconds(10));
+        play_sans(&mut game, &["e4"]);
+        assert_eq!(game.players.0.time, seconds(70 - 10 + 5));
+        // A repeating last stage adds its time again every period
+        let (mut game, source) = clocked_game(STARTING_FEN, "1/10");
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            source.advance(seconds(4));
+            play_sans(&mut game, &[san]);
+        }
+        assert_eq!(game.players.0.time, seconds(10 - 4 + 10 - 4 + 10));
+    }
+
+    #[test]
+    fn clock_flag() {
+        let (mut game, source) = clocked_game(STARTING_FEN, "60");
+        source.advance(Duration::from_secs(59));
+        assert!(!game.check_flag());
+        source.advance(Duration::from_secs(1));
+        let chess_move = Move::try_from(("e4", &game.board)).unwrap();
+        assert_eq!(game.play_move(chess_move).unwrap_err(), MoveError::GameOver);
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Timeout)));
+        assert!(game.turns.is_empty());
+        // The flag only loses when the opponent could still mate
+        let (mut game, source) = clocked_game("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "60");
+        source.advance(Duration::from_secs(61));
+        assert!(game.check_flag());
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::Timeout)));
+        let (mut game, source) = clocked_game("4k3/8/8/8/8/8/4P3/3nK3 w - - 0 1", "60");
+        source.advance(Duration::from_secs(61));
+        assert!(game.check_flag());
+        assert_eq!(game.result, GameResult::BlackWins);
+        let mating = |fen: &str, color: Color| Board::from_fen(fen).unwrap().has_mating_material(color);
+        assert!(!mating("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", Color::White));
+        assert!(mating("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1", Color::White));
+        assert!(!mating("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", Color::White));
+        assert!(!mating("5b2/4k3/8/8/8/8/8/2B1K3 w - - 0 1", Color::White));
+        assert!(mating("2b5/4k3/8/8/8/8/8/2B1K3 w - - 0 1", Color::White));
+        assert!(mating("4k3/4p3/8/8/8/8/8/1N2K3 w - - 0 1", Color::White));
+    }
+
+    #[test]
+    fn scripted_controllers() {
+        let mut game = Game::new(0);
+        // UCI and SAN are both accepted
+        game.play(&mut ScriptedController::new(&["f2f3", "g4"]), &mut ScriptedController::new(&["e5", "Qh4#"]));
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Checkmate)));
+        assert_eq!(game.turns_to_string(), "1. f3 e5; 2. g4 Qh4#");
+        // A move that does not parse abandons the game
+        let mut game = Game::new(0);
+        game.play(&mut ScriptedController::new(&["f3", "Ke3", "g4"]), &mut ScriptedController::new(&["e5", "Qh4#"]));
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Abandonment)));
+        assert_eq!(game.turns_to_string(), "1. f3 e5");
+        let mut game = Game::new(0);
+        game.play(&mut ScriptedController::new(&["e4"]), &mut ScriptedController::new(&["e5", "Nc6"]));
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Abandonment)));
+        // Threefold repetition is only a draw when claimed, so the script runs out first
+        let mut game = Game::new(0);
+        game.play(&mut ScriptedController::new(&["Nf3", "Ng1", "Nf3", "Ng1"]), &mut ScriptedController::new(&["Nf6", "Ng8", "Nf6", "Ng8"]));
+        assert_eq!(game.termination, Some(Termination::Abandonment));
+        // An engine claims it when behind and plays on when ahead
+        let mut engine = EngineController::new(SearchLimits { depth: Some(1), ..Default::default() });
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"];
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1", 0).unwrap();
+        play_sans(&mut game, &shuffle);
+        game.play(&mut engine, &mut RandomMover::new(1));
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::ThreefoldRepetition)));
+        let mut game = Game::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0).unwrap();
+        play_sans(&mut game, &shuffle);
+        assert!(matches!(engine.choose(&game), Action::Move(_)));
+    }
+
+    #[test]
+    fn random_controllers() {
+        let play = |seed: u64| {
+            let mut game = Game::new(0);
+            game.play(&mut RandomMover::new(seed), &mut RandomMover::new(seed + 1));
+            game
+        };
+        let (first, second) = (play(3), play(3));
+        assert!(first.is_over());
+        assert_eq!(first.turns_to_string(), second.turns_to_string());
+        assert_ne!(first.turns_to_string(), play(4).turns_to_string());
+    }
+
+    #[test]
+    fn terminal_transcript() {
+        let mut game = Game::new(0);
+        let mut output = Vec::new();
+        game.run("f3\n\ne5\nKe2?\ng4\nQh5\ndraw\nQh4#\n".as_bytes(), &mut output);
+        assert_eq!((game.result, game.termination), (GameResult::BlackWins, Some(Termination::Checkmate)));
+        let prompt = |game: &Game, color: Color| format!("{}\n{}\nEnter move for {}: ", game.turns_to_string(), game.board.to_string(color.into()), color.to_string().to_lowercase());
+        let mut replay = Game::new(0);
+        let mut expected = String::new();
+        expected += &prompt(&replay, Color::White);
+        play_sans(&mut replay, &["f3"]);
+        expected += &prompt(&replay, Color::Black);
+        play_sans(&mut replay, &["e5"]);
+        // Unparsable input and a move that cannot be played both ask again
+        expected += &prompt(&replay, Color::White);
+        expected += &format!("{}\n", MoveError::Invalid.as_str());
+        expected += &prompt(&replay, Color::White);
+        play_sans(&mut replay, &["g4"]);
+        expected += &prompt(&replay, Color::Black);
+        expected += &format!("{}\n", MoveError::Invalid.as_str());
+        expected += &prompt(&replay, Color::Black);
+        expected += "No draw can be claimed!\n";
+        expected += &prompt(&replay, Color::Black);
+        play_sans(&mut replay, &["Qh4#"]);
+        expected += &format!("{}\nCheckmate, Black wins the game!\n", replay.turns_to_string());
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        // Closing the input abandons the game
+        let mut game = Game::new(0);
+        let mut output = Vec::new();
+        game.run("e4\n".as_bytes(), &mut output);
+        assert_eq!((game.result, game.termination), (GameResult::WhiteWins, Some(Termination::Abandonment)));
+        assert!(String::from_utf8(output).unwrap().ends_with("White wins by abandonment!\n"));
+    }
+
+    #[test]
+    fn unmake_moves() {
+        // Unmaking restores the FEN, clocks included, and the hash after castling, en passant and capturing promotions
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 12 40",
+            "r3k2r/1P6/8/8/8/8/6p1/R3K2R b KQkq - 0 30",
+        ] {
+            walk_moves(&Board::from_fen(fen).unwrap(), 2, &|board, chess_move| {
+                let mut child = board.clone();
+                let undo = child.apply_move(chess_move, board.side_to_move);
+                child.unmake_move(chess_move, undo);
+                assert_eq!(child.to_fen(), board.to_fen(), "{}", chess_move.to_uci());
+                assert_eq!(child.zobrist(), board.zobrist(), "{}", chess_move.to_uci());
+            });
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::new(0);
+        assert_eq!(game.undo().unwrap_err(), MoveError::NothingToUndo);
+        play_sans(&mut game, &["e4", "d5", "exd5", "Qxd5"]);
+        let fen = game.board.to_fen();
+        assert_eq!(game.undo().unwrap().notation, "Qxd5");
+        assert_eq!(game.undo().unwrap().notation, "exd5");
+        assert_eq!(game.turns_to_string(), "1. e4 d5");
+        assert_eq!(game.board.to_fen(), "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2");
+        assert_eq!(game.positions.len(), 3);
+        game.redo().unwrap();
+        game.redo().unwrap();
+        assert_eq!(game.board.to_fen(), fen);
+        assert_eq!(game.redo().unwrap_err(), MoveError::NothingToRedo);
+        // Playing a different move forgets what was taken back
+        game.undo().unwrap();
+        play_sans(&mut game, &["Nf6"]);
+        assert_eq!(game.redo().unwrap_err(), MoveError::NothingToRedo);
+        assert_eq!(game.turns_to_string(), "1. e4 d5; 2. exd5 Nf6");
+        // A game ended by its last move is reopened, one ended by a player is not
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["f3", "e5", "g4", "Qh4#"]);
+        game.undo().unwrap();
+        assert_eq!((game.result, game.termination), (GameResult::Ongoing, None));
+        assert!(!game.players.0.is_checked());
+        game.redo().unwrap();
+        assert_eq!(game.result, GameResult::BlackWins);
+        assert!(game.players.0.is_checked());
+        let mut game = Game::new(0);
+        play_sans(&mut game, &["e4"]);
+        game.resign(Color::Black);
+        assert_eq!(game.undo().unwrap_err(), MoveError::GameOver);
+        // A position set up from FEN can be taken back to but not beyond
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1", 0).unwrap();
+        play_sans(&mut game, &["Kd7", "e4"]);
+        assert_eq!(game.take_back(3).unwrap_err(), MoveError::NothingToUndo);
+        game.take_back(2).unwrap();
+        assert!(game.turns.is_empty());
+        assert_eq!(game.board.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn undo_clock() {
+        let (mut game, source) = clocked_game(STARTING_FEN, "300+2");
+        source.advance(Duration::from_secs(10));
+        play_sans(&mut game, &["e4"]);
+        source.advance(Duration::from_secs(20));
+        play_sans(&mut game, &["e5"]);
+        assert_eq!((game.players.0.time, game.players.1.time), (Duration::from_secs(292), Duration::from_secs(282)));
+        game.take_back(2).unwrap();
+        assert_eq!((game.players.0.time, game.players.1.time), (Duration::from_secs(300), Duration::from_secs(300)));
+        // The turn starts over rather than charging the time spent before the takeback
+        let clock = game.clock.as_ref().unwrap();
+        assert_eq!(clock.remaining(&game.players.0), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn terminal_takeback() {
+        let mut game = Game::new(0);
+        let mut output = Vec::new();
+        game.run("e4\ne5\ntakeback\nyes\nd4\nundo\nn\nd5\nundo\ny\nresign\n".as_bytes(), &mut output);
+        assert_eq!(game.turns_to_string(), "1. d4");
+        assert_eq!((game.result, game.termination), (GameResult::WhiteWins, Some(Termination::Resignation)));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Enter move for white: White asks to take back the last turn, accept? (y/n): "));
+        assert!(output.contains("Enter move for black: Black asks to take back the last move, accept? (y/n): Takeback was declined!\n"));
+        // Nothing can be taken back before the first move
+        let mut game = Game::new(0);
+        let mut output = Vec::new();
+        game.run("undo\n".as_bytes(), &mut output);
+        assert!(String::from_utf8(output).unwrap().contains("Enter move for white: No move to take back!\n"));
+    }
+
+    #[test]
+    fn pgn_variations() {
+        let pgn = "[Event \"Analysis\"]\n[Result \"*\"]\n\n{Opening notes} 1. e4 {[%eval 0.3] [%clk 0:05:00] Best by\ntest} e5 (1... c5 {Sharper} 2. Nf3 (2. c3 d5) 2... d6 $14) 2. Nf3! Nc6?! $32 3. Bb5 *\n";
+        let tree = GameTree::from_pgn(pgn).unwrap();
+        let e4 = &tree.children[0];
+        assert_eq!((e4.eval, e4.clock), (Some(Evaluation::Centipawns(30)), Some(Duration::from_secs(300))));
+        assert_eq!(e4.comment.as_deref(), Some("Best by test"));
+        let sicilian = &e4.children[1];
+        assert_eq!((sicilian.chess_move.notation.as_str(), sicilian.children.len()), ("c5", 2));
+        assert_eq!(sicilian.children[0].children[0].nags, vec![14]);
+        let expected = "{Opening notes} 1. e4 {[%eval 0.30] [%clk 0:05:00] Best by test} 1... e5\n(1... c5 {Sharper} 2. Nf3 (2. c3 d5) 2... d6 $14) 2. Nf3! Nc6?! $32 3. Bb5 *\n";
+        let written = tree.to_pgn();
+        assert!(written.ends_with(&format!("[Result \"*\"]\n\n{}", expected)), "{}", written);
+        assert_eq!(GameTree::from_pgn(&written).unwrap().to_pgn(), written);
+        // The game read alongside keeps only the main line
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.turns_to_string(), "1. e4 e5; 2. Nf3 Nc6; 3. Bb5");
+        // A result without a Termination tag survives the trip through a tree
+        let game = GameTree::from_pgn("1. e4 e5 2. Qh5 Ke7 1-0").unwrap().to_game().unwrap();
+        assert_eq!((game.result, game.termination), (GameResult::WhiteWins, None));
+        // Records may play on past an automatic draw, which still decides the game
+        let pgn = "[SetUp \"1\"]\n[FEN \"k7/8/8/8/8/8/8/K6R w - - 149 80\"]\n\n80. Rh2 Kb8 81. Rh1 Ka8 1/2-1/2\n";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.turns_to_string(), "1. Rh2 Kb8; 2. Rh1 Ka8");
+        assert_eq!((game.result, game.termination), (GameResult::Draw, Some(Termination::SeventyFiveMove)));
+        let game = GameTree::from_pgn(pgn).unwrap().to_game().unwrap();
+        assert_eq!((game.turns.len(), game.termination), (2, Some(Termination::SeventyFiveMove)));
+        // Moves in variations are checked too, and a variation needs a move to replace
+        let err = GameTree::from_pgn("1. e4 (1. e5) e5 *").err().unwrap();
+        assert_eq!((err.line, err.column, err.kind), (1, 11, PgnErrorKind::Move("e5".to_string(), MoveError::Invalid)));
+        assert_eq!(GameTree::from_pgn("(1. d4) 1. e4 *").err().unwrap().kind, PgnErrorKind::UnbalancedVariation);
+    }
+
+    #[test]
+    fn game_tree_navigation() {
+        let mut tree = GameTree::from(&Game::from_pgn("1. e4 e5 2. Nf3 Nc6 3. Bb5 *").unwrap());
+        assert!(tree.go_to_ply(2));
+        assert_eq!(tree.current().unwrap().chess_move.notation, "e5");
+        assert!(!tree.go_to_ply(9));
+        assert_eq!(tree.ply(), 2);
+        // A new move starts a variation, a known one is followed
+        assert_eq!(tree.play_move(Move::try_from(("e4", &Board::default())).unwrap()), Err(MoveError::Invalid));
+        tree.play_move(Move::try_from(("Nc3", tree.board())).unwrap()).unwrap();
+        assert_eq!(tree.path(), [0, 0, 1]);
+        tree.play_move(Move::try_from(("Nf6", tree.board())).unwrap()).unwrap();
+        let fen = tree.board().to_fen();
+        tree.back();
+        tree.back();
+        tree.play_move(Move::try_from(("Nc3", tree.board())).unwrap()).unwrap();
+        assert!(tree.forward());
+        assert_eq!(tree.board().to_fen(), fen);
+        assert_eq!(tree.continuations().len(), 0);
+        // Promoting makes the line through the cursor the main line
+        assert!(tree.promote_variation());
+        assert_eq!(tree.path(), [0, 0, 0, 0]);
+        assert_eq!(tree.mainline().iter().map(|chess_move| chess_move.notation.as_str()).collect::<Vec<&str>>(), ["e4", "e5", "Nc3", "Nf6"]);
+        assert!(!tree.promote_variation());
+        assert_eq!(tree.to_game().unwrap().turns_to_string(), "1. e4 e5; 2. Nc3 Nf6");
+        // Deleting removes the move at the cursor and the rest of its line
+        tree.go_to_ply(2);
+        assert!(tree.forward_to(1));
+        assert_eq!(tree.current().unwrap().chess_move.notation, "Nf3");
+        assert!(tree.delete_variation());
+        assert_eq!(tree.ply(), 2);
+        assert_eq!(tree.continuations().len(), 1);
+        assert!(tree.to_pgn().ends_with("1. e4 e5 2. Nc3 Nf6 *\n"));
+        tree.go_to_ply(0);
+        assert!(!tree.back() && !tree.delete_variation());
+        assert_eq!(tree.board().to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn positions() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let position = Position::try_from(fen).unwrap();
+        assert_eq!(position.to_string(), fen);
+        assert_eq!(Position::default().to_string(), STARTING_FEN);
+        assert_eq!(position.zobrist(), Board::from_fen(fen).unwrap().zobrist());
+        assert_eq!(Position::from(&Board::from(position)), position);
+        assert_eq!(position.piece(Coordinate::from(('e', '1'))), Some(Piece::new(Color::White, PieceType::King)));
+        assert_eq!(position.castling_rights(Color::Black), CastlingRights::Both);
+        // Copies are independent and equal positions hash alike
+        let mut copy = position;
+        copy.set_piece(Coordinate::from(('e', '5')), None);
+        assert_ne!(copy, position);
+        let mut seen = std::collections::HashSet::new();
+        seen.insert(position);
+        assert!(seen.contains(&Position::try_from(fen).unwrap()));
+        assert!(!seen.contains(&copy));
+        // Transposing into the same position with the same counters gives equal values
+        let play = |sans: &[&str]| sans.iter().fold(Position::default(), |position, san| {
+            let chess_move = Move::try_from((*san, &Board::from(position))).unwrap();
+            position.after(&chess_move).unwrap()
+        });
+        assert_eq!(play(&["Nf3", "Nf6", "Nc3", "Nc6"]), play(&["Nc3", "Nc6", "Nf3", "Nf6"]));
+        assert_ne!(play(&["e4", "e5", "Nf3"]), play(&["Nf3", "e5", "e4"]));
+        assert_eq!(play(&["e4", "d5", "e5", "f5"]).en_passant, Some(Coordinate::from(('f', '6'))));
+        // Illegal moves are refused rather than applied
+        let pinned = Position::try_from("4r1k1/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let chess_move = Move::new(PieceType::Rook, Coordinate::from(('e', '2')), Coordinate::from(('a', '2')), MoveType::Normal, None);
+        assert_eq!(pinned.after(&chess_move), Err(MoveError::Pinned));
+        // A position can be searched on its own
+        let mate = Position::try_from("1r4k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(mate.legal_moves().len(), Board::from(mate).legal_moves(Color::White).len());
+        let info = search::search(&Board::from(mate), SearchLimits { depth: Some(4), ..Default::default() }, &EvalWeights::default(), &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {});
+        assert_eq!(info.mate_in(), Some(2));
+    }
+}
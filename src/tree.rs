@@ -0,0 +1,293 @@
+use std::{fmt::{self, Display}, time::Duration};
+
+use crate::{board::Board, game::{Game, GameResult, Move, MoveError, Player, Termination}};
+
+// Engine evaluation as written in a [%eval] comment, from White's point of view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Evaluation {
+	Centipawns(i32),
+	Mate(i32),
+}
+
+impl TryFrom<&str> for Evaluation {
+	type Error = ();
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		match value.strip_prefix('#') {
+			Some(moves) => moves.parse().map(Evaluation::Mate).map_err(|_| ()),
+			None => value.parse::<f64>().map(|pawns| Evaluation::Centipawns((pawns * 100.0).round() as i32)).map_err(|_| ()),
+		}
+	}
+}
+
+impl Display for Evaluation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Evaluation::Centipawns(centipawns) => write!(f, "{:.2}", *centipawns as f64 / 100.0),
+			Evaluation::Mate(moves) => write!(f, "#{}", moves),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+	pub chess_move: Move,
+	pub comment: Option<String>,
+	// Numeric annotation glyphs, 1 to 6 being !, ?, !!, ??, !? and ?!
+	pub nags: Vec<u8>,
+	pub clock: Option<Duration>,
+	pub eval: Option<Evaluation>,
+	// The first continuation is the main line, any others are variations
+	pub children: Vec<Node>,
+}
+
+impl Node {
+	pub fn new(chess_move: Move) -> Self {
+		Self {
+			chess_move,
+			comment: None,
+			nags: Vec::new(),
+			clock: None,
+			eval: None,
+			children: Vec::new(),
+		}
+	}
+	// Reads [%clk] and [%eval] commands out of a comment, keeping the rest of it as text
+	pub fn annotate(&mut self, comment: &str) {
+		let mut text = String::new();
+		let mut rest = comment;
+		while let Some(start) = rest.find("[%") {
+			let Some(end) = rest[start..].find(']').map(|end| start + end) else { break };
+			let (command, value) = rest[start + 2..end].trim().split_once(char::is_whitespace).unwrap_or((&rest[start + 2..end], ""));
+			let parsed = match command {
+				"clk" => parse_clock(value.trim()).map(|clock| self.clock = Some(clock)),
+				"eval" => Evaluation::try_from(value.trim()).ok().map(|eval| self.eval = Some(eval)),
+				_ => None,
+			};
+			text.push_str(&rest[..start]);
+			if parsed.is_none() {
+				text.push_str(&rest[start..=end]);
+			}
+			rest = &rest[end + 1..];
+		}
+		text.push_str(rest);
+		let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+		if !text.is_empty() {
+			self.comment = Some(match self.comment.take() {
+				Some(comment) => format!("{} {}", comment, text),
+				None => text,
+			});
+		}
+	}
+	// The comment as written in PGN, with any clock and evaluation as commands
+	pub fn full_comment(&self) -> Option<String> {
+		let mut parts = Vec::<String>::new();
+		if let Some(eval) = self.eval {
+			parts.push(format!("[%eval {}]", eval));
+		}
+		if let Some(clock) = self.clock {
+			let seconds = clock.as_secs();
+			parts.push(format!("[%clk {}:{:02}:{:02}]", seconds / 3600, seconds / 60 % 60, seconds % 60));
+		}
+		parts.extend(self.comment.clone());
+		(!parts.is_empty()).then(|| parts.join(" "))
+	}
+}
+
+fn parse_clock(value: &str) -> Option<Duration> {
+	let seconds = value.split(':').try_fold(0.0, |total, part| part.parse::<f64>().ok().map(|part| total * 60.0 + part))?;
+	(seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+// A game with its variations, browsed with a cursor that has its own board
+pub struct GameTree {
+	pub tags: Vec<(String, String)>,
+	pub result: GameResult,
+	pub termination: Option<Termination>,
+	// Comment before the first move
+	pub comment: Option<String>,
+	pub children: Vec<Node>,
+	start: Board,
+	// Which continuation was taken at each ply to reach the cursor
+	path: Vec<usize>,
+	board: Board,
+}
+
+impl GameTree {
+	pub fn new(start: Board) -> Self {
+		Self {
+			tags: Vec::new(),
+			result: GameResult::Ongoing,
+			termination: None,
+			comment: None,
+			children: Vec::new(),
+			board: start.clone(),
+			start,
+			path: Vec::new(),
+		}
+	}
+	pub fn start(&self) -> &Board {
+		&self.start
+	}
+	pub fn board(&self) -> &Board {
+		&self.board
+	}
+	pub fn ply(&self) -> usize {
+		self.path.len()
+	}
+	// The continuation indices from the start to the cursor, one per ply
+	pub fn path(&self) -> &[usize] {
+		&self.path
+	}
+	pub fn go_to_path(&mut self, path: &[usize]) -> bool {
+		let mut children = &self.children;
+		for index in path {
+			let Some(node) = children.get(*index) else { return false };
+			children = &node.children;
+		}
+		self.path = path.to_vec();
+		self.replay();
+		true
+	}
+	// The node of the last move played to reach the cursor, None at the start
+	pub fn current(&self) -> Option<&Node> {
+		let (last, path) = self.path.split_last()?;
+		Some(&self.children_at(path)[*last])
+	}
+	pub fn current_mut(&mut self) -> Option<&mut Node> {
+		let mut path = self.path.clone();
+		let last = path.pop()?;
+		Some(&mut self.children_at_mut(&path)[last])
+	}
+	// The moves that can follow the cursor, main line first
+	pub fn continuations(&self) -> &[Node] {
+		self.children_at(&self.path)
+	}
+	pub fn mainline(&self) -> Vec<&Move> {
+		let mut moves = Vec::new();
+		let mut children = &self.children;
+		while let Some(node) = children.first() {
+			moves.push(&node.chess_move);
+			children = &node.children;
+		}
+		moves
+	}
+	// Follows a continuation, 0 being the main line
+	pub fn forward_to(&mut self, index: usize) -> bool {
+		let Some(node) = self.continuations().get(index) else { return false };
+		let chess_move = node.chess_move.clone();
+		self.board.apply_move(&chess_move, self.board.side_to_move);
+		self.path.push(index);
+		true
+	}
+	pub fn forward(&mut self) -> bool {
+		self.forward_to(0)
+	}
+	pub fn back(&mut self) -> bool {
+		if self.path.pop().is_none() {
+			return false;
+		}
+		self.replay();
+		true
+	}
+	// Moves along the current line, which continues with the main line past the cursor
+	pub fn go_to_ply(&mut self, ply: usize) -> bool {
+		if ply <= self.path.len() {
+			self.path.truncate(ply);
+			self.replay();
+			return true;
+		}
+		let path = self.path.clone();
+		while self.path.len() < ply {
+			if !self.forward() {
+				self.path = path;
+				self.replay();
+				return false;
+			}
+		}
+		true
+	}
+	// Plays a move from the cursor, following it if it is already in the tree and adding a variation if not
+	pub fn play_move(&mut self, mut chess_move: Move) -> Result<(), MoveError> {
+		if let Some(index) = self.continuations().iter().position(|node| node.chess_move.is_same(&chess_move)) {
+			self.forward_to(index);
+			return Ok(());
+		}
+		let mut board = self.board.clone();
+		let color = board.side_to_move;
+		chess_move.notation = board.to_san(&chess_move);
+		board.execute_move(&chess_move, &mut Player::new(color, 0))?;
+		chess_move.is_check = board.is_in_check(color.opponent());
+		chess_move.is_mate = chess_move.is_check && !board.has_legal_move(color.opponent());
+		let path = self.path.clone();
+		let children = self.children_at_mut(&path);
+		children.push(Node::new(chess_move));
+		let index = children.len() - 1;
+		self.path.push(index);
+		self.board = board;
+		Ok(())
+	}
+	// Makes the line through the cursor the main line at every branch leading to it
+	pub fn promote_variation(&mut self) -> bool {
+		if self.path.iter().all(|index| *index == 0) {
+			return false;
+		}
+		for depth in 0..self.path.len() {
+			let (path, index) = (self.path[..depth].to_vec(), self.path[depth]);
+			let node = self.children_at_mut(&path).remove(index);
+			self.children_at_mut(&path).insert(0, node);
+			self.path[depth] = 0;
+		}
+		true
+	}
+	// Removes the move at the cursor and everything after it, moving back to the position before it
+	pub fn delete_variation(&mut self) -> bool {
+		let Some(index) = self.path.pop() else { return false };
+		let path = self.path.clone();
+		self.children_at_mut(&path).remove(index);
+		self.replay();
+		true
+	}
+	fn children_at(&self, path: &[usize]) -> &Vec<Node> {
+		path.iter().fold(&self.children, |children, index| &children[*index].children)
+	}
+	fn children_at_mut(&mut self, path: &[usize]) -> &mut Vec<Node> {
+		path.iter().fold(&mut self.children, |children, index| &mut children[*index].children)
+	}
+	fn replay(&mut self) {
+		let mut board = self.start.clone();
+		let mut children = &self.children;
+		for index in &self.path {
+			let node = &children[*index];
+			board.apply_move(&node.chess_move, board.side_to_move);
+			children = &node.children;
+		}
+		self.board = board;
+	}
+	// The main line as a game, without its variations and annotations
+	pub fn to_game(&self) -> Result<Game, MoveError> {
+		let mut game = Game::from_fen(&self.start.to_fen(), 0).expect("board gives a valid FEN");
+		game.tags = self.tags.clone();
+		for chess_move in self.mainline() {
+			game.replay_move(chess_move.clone())?;
+		}
+		game.end_recorded(self.result, self.termination);
+		Ok(game)
+	}
+}
+
+impl From<&Game> for GameTree {
+	fn from(game: &Game) -> Self {
+		let start = game.tag("FEN").and_then(|fen| Board::from_fen(fen).ok()).unwrap_or_default();
+		let mut tree = GameTree::new(start);
+		tree.tags = game.tags.clone();
+		tree.result = game.result;
+		tree.termination = game.termination;
+		let mut children = &mut tree.children;
+		for chess_move in game.turns.iter().flat_map(|turn| [&turn.0, &turn.1]).flatten() {
+			children.push(Node::new(chess_move.clone()));
+			children = &mut children[0].children;
+		}
+		tree
+	}
+}
@@ -0,0 +1,147 @@
+use std::mem::size_of;
+
+use crate::{board::Coordinate, game::Move, pieces::PieceType, search::MATE_SCORE};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+// Scores this close to mate are distances from the root, see `to_table` and `from_table`
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+	Exact,
+	// The score is at least this good, the search failed high
+	Lower,
+	// The score is at most this good, the search failed low
+	Upper,
+}
+
+// Enough to recognise the move again among the legal moves of the position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TtMove {
+	pub from: Coordinate,
+	pub to: Coordinate,
+	pub promotion: Option<PieceType>,
+}
+
+impl TtMove {
+	pub fn matches(&self, chess_move: &Move) -> bool {
+		self.from == chess_move.from && self.to == chess_move.to && self.promotion == chess_move.promotion
+	}
+}
+
+impl From<&Move> for TtMove {
+	fn from(chess_move: &Move) -> Self {
+		Self {
+			from: chess_move.from,
+			to: chess_move.to,
+			promotion: chess_move.promotion,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TtEntry {
+	pub key: u64,
+	pub depth: u32,
+	pub bound: Bound,
+	pub score: i32,
+	pub best_move: Option<TtMove>,
+	generation: u8,
+}
+
+pub struct TranspositionTable {
+	entries: Vec<Option<TtEntry>>,
+	generation: u8,
+}
+
+impl TranspositionTable {
+	pub fn new(megabytes: usize) -> Self {
+		let mut table = Self { entries: Vec::new(), generation: 0 };
+		table.resize(megabytes);
+		table
+	}
+	pub fn resize(&mut self, megabytes: usize) {
+		let count = megabytes * 1024 * 1024 / size_of::<Option<TtEntry>>();
+		self.entries = vec![None; count];
+	}
+	pub fn clear(&mut self) {
+		self.entries.fill(None);
+		self.generation = 0;
+	}
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+	// Called once per search so entries from earlier searches are replaced first
+	pub fn new_search(&mut self) {
+		self.generation = self.generation.wrapping_add(1);
+	}
+	pub fn probe(&self, key: u64, ply: u32) -> Option<TtEntry> {
+		let entry = (*self.entries.get(self.index(key)?)?)?;
+		if entry.key != key {
+			return None;
+		}
+		Some(TtEntry { score: from_table(entry.score, ply), ..entry })
+	}
+	pub fn store(&mut self, key: u64, depth: u32, bound: Bound, score: i32, best_move: Option<TtMove>, ply: u32) {
+		let Some(index) = self.index(key) else { return };
+		let generation = self.generation;
+		let slot = &mut self.entries[index];
+		// Prefer keeping deeper results from the current search, but never a stale one
+		let replace = match slot {
+			Some(existing) => existing.key == key || existing.generation != generation || depth >= existing.depth || bound == Bound::Exact,
+			None => true,
+		};
+		if !replace {
+			return;
+		}
+		// Keep the old move when a fail-low found nothing better to remember
+		let best_move = best_move.or(match slot {
+			Some(existing) if existing.key == key => existing.best_move,
+			_ => None,
+		});
+		*slot = Some(TtEntry {
+			key,
+			depth,
+			bound,
+			score: to_table(score, ply),
+			best_move,
+			generation,
+		});
+	}
+	fn index(&self, key: u64) -> Option<usize> {
+		if self.entries.is_empty() {
+			return None;
+		}
+		Some((key % self.entries.len() as u64) as usize)
+	}
+}
+
+impl Default for TranspositionTable {
+	fn default() -> Self {
+		Self::new(DEFAULT_HASH_MB)
+	}
+}
+
+// Mate scores are stored relative to the node rather than the root, so they stay right when reached through another path
+fn to_table(score: i32, ply: u32) -> i32 {
+	if score > MATE_THRESHOLD {
+		score + ply as i32
+	} else if score < -MATE_THRESHOLD {
+		score - ply as i32
+	} else {
+		score
+	}
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+	if score > MATE_THRESHOLD {
+		score - ply as i32
+	} else if score < -MATE_THRESHOLD {
+		score + ply as i32
+	} else {
+		score
+	}
+}
@@ -0,0 +1,200 @@
+use std::{io::{BufRead, Write}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::{self, JoinHandle}, time::Duration};
+
+use crate::{board::Color, eval::{self, EvalWeights}, game::{Game, Move, Player}, search::{self, SearchInfo, SearchLimits}, tt::{TranspositionTable, DEFAULT_HASH_MB}};
+
+const MAX_HASH_MB: usize = 4096;
+
+#[derive(Clone, Copy, Default)]
+struct GoOptions {
+	depth: Option<u32>,
+	nodes: Option<u64>,
+	movetime: Option<Duration>,
+	wtime: Option<Duration>,
+	btime: Option<Duration>,
+	winc: Option<Duration>,
+	binc: Option<Duration>,
+	infinite: bool,
+}
+
+impl GoOptions {
+	fn parse(arguments: &[&str]) -> Self {
+		let mut options = GoOptions::default();
+		let mut arguments = arguments.iter();
+		while let Some(&argument) = arguments.next() {
+			let mut value = || arguments.next().and_then(|value| value.parse::<u64>().ok());
+			match argument {
+				"depth" => options.depth = value().map(|depth| depth as u32),
+				"nodes" => options.nodes = value(),
+				"movetime" => options.movetime = value().map(Duration::from_millis),
+				"wtime" => options.wtime = value().map(Duration::from_millis),
+				"btime" => options.btime = value().map(Duration::from_millis),
+				"winc" => options.winc = value().map(Duration::from_millis),
+				"binc" => options.binc = value().map(Duration::from_millis),
+				"infinite" => options.infinite = true,
+				_ => {},
+			}
+		}
+		options
+	}
+	fn limits(&self, color: Color) -> SearchLimits {
+		let (time, increment) = match color {
+			Color::White => (self.wtime, self.winc),
+			Color::Black => (self.btime, self.binc),
+		};
+		// Spend a fraction of the remaining clock plus most of the increment, never running it down
+		let budget = time.map(|time| {
+			let increment = increment.unwrap_or_default();
+			(time / 30 + increment * 3 / 4).min(time / 2)
+		});
+		SearchLimits {
+			depth: self.depth,
+			nodes: self.nodes,
+			time: if self.infinite { None } else { self.movetime.or(budget) },
+		}
+	}
+}
+
+struct Search {
+	stop: Arc<AtomicBool>,
+	handle: JoinHandle<()>,
+}
+
+impl Search {
+	fn finish(self) {
+		self.stop.store(true, Ordering::Relaxed);
+		let _ = self.handle.join();
+	}
+}
+
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+	let output = Arc::new(Mutex::new(output));
+	let mut game = Game::new(0);
+	let mut weights = Arc::new(EvalWeights::default());
+	let tt = Arc::new(Mutex::new(TranspositionTable::new(DEFAULT_HASH_MB)));
+	let mut search: Option<Search> = None;
+	for line in input.lines() {
+		let Ok(line) = line else { break };
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		match tokens.first().copied() {
+			Some("uci") => {
+				send(&output, &format!("id name chess_rs {}", env!("CARGO_PKG_VERSION")));
+				send(&output, "id author chess_rs contributors");
+				send(&output, &format!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB));
+				send(&output, "option name EvalFile type string default <empty>");
+				send(&output, "uciok");
+			},
+			Some("isready") => send(&output, "readyok"),
+			Some("setoption") => {
+				if let Some(search) = search.take() {
+					search.finish();
+				}
+				let (name, value) = parse_option(&tokens[1..]);
+				if name.eq_ignore_ascii_case("Hash") {
+					if let Ok(megabytes) = value.parse::<usize>() {
+						tt.lock().unwrap().resize(megabytes.clamp(1, MAX_HASH_MB));
+					}
+				} else if name.eq_ignore_ascii_case("EvalFile") {
+					match value.as_str() {
+						"" | "<empty>" => weights = Arc::new(EvalWeights::default()),
+						path => match EvalWeights::load(path) {
+							Ok(loaded) => weights = Arc::new(loaded),
+							Err(err) => send(&output, &format!("info string {}", err)),
+						},
+					}
+				}
+			},
+			Some("ucinewgame") => {
+				if let Some(search) = search.take() {
+					search.finish();
+				}
+				tt.lock().unwrap().clear();
+				game = Game::new(0);
+			},
+			Some("position") => {
+				if let Some(search) = search.take() {
+					search.finish();
+				}
+				match set_position(&tokens[1..]) {
+					Ok(position) => game = position,
+					Err(err) => send(&output, &format!("info string {}", err)),
+				}
+			},
+			Some("go") => {
+				if let Some(search) = search.take() {
+					search.finish();
+				}
+				let options = GoOptions::parse(&tokens[1..]);
+				let stop = Arc::new(AtomicBool::new(false));
+				let board = game.board.clone();
+				let (thread_stop, thread_output, thread_weights) = (stop.clone(), output.clone(), weights.clone());
+				let thread_tt = tt.clone();
+				let limits = options.limits(board.side_to_move);
+				let handle = thread::spawn(move || {
+					let result = search::search(&board, limits, &thread_weights, &mut thread_tt.lock().unwrap(), &thread_stop, |info| send(&thread_output, &info_line(info)));
+					// UCI forbids answering an infinite search before being told to stop
+					while options.infinite && !thread_stop.load(Ordering::Relaxed) {
+						thread::sleep(Duration::from_millis(1));
+					}
+					let best_move = result.best_move().map_or("0000".to_string(), |chess_move| chess_move.to_uci());
+					send(&thread_output, &format!("bestmove {}", best_move));
+				});
+				search = Some(Search { stop, handle });
+			},
+			Some("stop") => {
+				if let Some(search) = search.take() {
+					search.finish();
+				}
+			},
+			Some("eval") => {
+				for line in eval::trace(&game.board, &weights).to_string().lines() {
+					send(&output, line);
+				}
+			},
+			Some("quit") => break,
+			_ => {},
+		}
+	}
+	if let Some(search) = search.take() {
+		search.finish();
+	}
+}
+
+// Option names and values may both contain spaces
+fn parse_option(tokens: &[&str]) -> (String, String) {
+	let value_index = tokens.iter().position(|token| *token == "value").unwrap_or(tokens.len());
+	let name = tokens.get(1..value_index).unwrap_or_default().join(" ");
+	let value = tokens.get(value_index + 1..).unwrap_or_default().join(" ");
+	(name, value)
+}
+
+fn info_line(info: &SearchInfo) -> String {
+	let score = match info.mate_in() {
+		Some(moves) => format!("mate {}", moves),
+		None => format!("cp {}", info.score),
+	};
+	let pv: Vec<String> = info.pv.iter().map(Move::to_uci).collect();
+	format!("info depth {} score {} nodes {} time {} pv {}", info.depth, score, info.nodes, info.elapsed.as_millis(), pv.join(" "))
+}
+
+// Moves are replayed on the board alone, since the GUI decides when the game is over
+fn set_position(tokens: &[&str]) -> Result<Game, String> {
+	let moves_index = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+	let mut game = match tokens.first() {
+		Some(&"startpos") => Game::new(0),
+		Some(&"fen") => Game::from_fen(&tokens[1..moves_index].join(" "), 0).map_err(|err| err.to_string())?,
+		_ => return Err("position needs startpos or fen".to_string()),
+	};
+	for uci in tokens.iter().skip(moves_index + 1) {
+		let color = game.board.side_to_move;
+		Move::from_uci(uci, &game.board)
+			.and_then(|chess_move| game.board.execute_move(&chess_move, &mut Player::new(color, 0)))
+			.map_err(|err| format!("{} {}", uci, err.as_str()))?;
+	}
+	Ok(game)
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+	let mut output = output.lock().unwrap();
+	let _ = writeln!(output, "{}", line);
+	let _ = output.flush();
+}
@@ -0,0 +1,154 @@
+use std::{io::{BufRead, Write}, sync::atomic::AtomicBool, time::Duration};
+
+use crate::{board::Color, eval::EvalWeights, fen::STARTING_FEN, game::{Game, Move, Termination}, search::{self, SearchLimits}, tt::{TranspositionTable, DEFAULT_HASH_MB}};
+
+// Used when neither a clock nor an explicit limit has been given
+const DEFAULT_DEPTH: u32 = 4;
+const FEATURES: &str = "feature myname=\"chess_rs\" setboard=1 usermove=1 ping=1 memory=1 sigint=0 sigterm=0 colors=0 done=1";
+
+struct Session {
+	game: Game,
+	engine_color: Option<Color>,
+	engine_time: Option<u32>,
+	opponent_time: Option<u32>,
+	max_depth: Option<u32>,
+	move_time: Option<Duration>,
+}
+
+impl Session {
+	fn new(fen: &str) -> Option<Self> {
+		Some(Self {
+			game: Game::from_fen(fen, 0).ok()?,
+			engine_color: None,
+			engine_time: None,
+			opponent_time: None,
+			max_depth: None,
+			move_time: None,
+		})
+	}
+	fn play(&mut self, chess_move: Move) -> bool {
+		self.game.play_move(chess_move).is_ok()
+	}
+	fn undo(&mut self, plies: usize) {
+		for _ in 0..plies {
+			if self.game.undo().is_err() {
+				break;
+			}
+		}
+	}
+	fn result(&self) -> Option<String> {
+		let comment = match (self.game.result.winner(), self.game.termination?) {
+			(Some(winner), Termination::Checkmate) => format!("{} mates", winner),
+			(Some(winner), termination) => format!("{} wins by {}", winner, termination.as_str()),
+			(None, termination) => format!("Draw by {}", termination.as_str()),
+		};
+		Some(format!("{} {{{}}}", self.game.result_token(), comment))
+	}
+	fn engine_move<W: Write>(&mut self, output: &mut W, tt: &mut TranspositionTable) {
+		if self.engine_color != Some(self.game.board.side_to_move) || self.result().is_some() {
+			return;
+		}
+		let (engine, opponent) = match self.game.board.side_to_move {
+			Color::White => (&mut self.game.players.0, &mut self.game.players.1),
+			Color::Black => (&mut self.game.players.1, &mut self.game.players.0),
+		};
+		// Xboard reports clocks in centiseconds
+		if let Some(time) = self.engine_time {
+			engine.time = Duration::from_millis(time as u64 * 10);
+		}
+		if let Some(time) = self.opponent_time {
+			opponent.time = Duration::from_millis(time as u64 * 10);
+		}
+		let budget = self.engine_time.map(|time| Duration::from_millis(time as u64 * 10 / 30));
+		let mut limits = SearchLimits {
+			depth: self.max_depth,
+			nodes: None,
+			time: self.move_time.or(budget),
+		};
+		if limits.depth.is_none() && limits.time.is_none() {
+			limits.depth = Some(DEFAULT_DEPTH);
+		}
+		let result = search::search(&self.game.board, limits, &EvalWeights::DEFAULT, tt, &AtomicBool::new(false), |_| {});
+		if let Some(chess_move) = result.best_move().cloned() {
+			let uci = chess_move.to_uci();
+			if self.play(chess_move) {
+				let _ = writeln!(output, "move {}", uci);
+			}
+		}
+		// Claim only when the search does not expect to win
+		if result.score <= 0 {
+			self.game.claim_draw();
+		}
+		if let Some(result) = self.result() {
+			let _ = writeln!(output, "{}", result);
+		}
+	}
+}
+
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W) {
+	let mut session = Session::new(STARTING_FEN).expect("starting position is valid");
+	let mut tt = TranspositionTable::new(DEFAULT_HASH_MB);
+	for line in input.lines() {
+		let Ok(line) = line else { break };
+		let (command, arguments) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+		match command {
+			"protover" => {
+				let _ = writeln!(output, "{}", FEATURES);
+			},
+			"new" => {
+				session = Session::new(STARTING_FEN).expect("starting position is valid");
+				session.engine_color = Some(Color::Black);
+				tt.clear();
+			},
+			"force" => session.engine_color = None,
+			"go" => {
+				session.engine_color = Some(session.game.board.side_to_move);
+				session.engine_move(&mut output, &mut tt);
+			},
+			"usermove" => {
+				let played = Move::from_uci(arguments, &session.game.board).is_ok_and(|chess_move| session.play(chess_move));
+				if !played {
+					let _ = writeln!(output, "Illegal move: {}", arguments);
+				} else if let Some(result) = session.result() {
+					let _ = writeln!(output, "{}", result);
+				} else {
+					session.engine_move(&mut output, &mut tt);
+				}
+			},
+			"setboard" => {
+				match Session::new(arguments) {
+					Some(mut position) => {
+						position.engine_color = None;
+						position.engine_time = session.engine_time;
+						position.opponent_time = session.opponent_time;
+						position.max_depth = session.max_depth;
+						position.move_time = session.move_time;
+						session = position;
+					},
+					None => {
+						let _ = writeln!(output, "tellusererror Illegal position");
+					},
+				}
+			},
+			"time" => session.engine_time = arguments.parse().ok(),
+			"otim" => session.opponent_time = arguments.parse().ok(),
+			// The table size in megabytes
+			"memory" => {
+				if let Ok(megabytes) = arguments.parse() {
+					tt.resize(megabytes);
+				}
+			},
+			"sd" => session.max_depth = arguments.parse().ok(),
+			"st" => session.move_time = arguments.parse().ok().map(Duration::from_secs),
+			"undo" => session.undo(1),
+			"remove" => session.undo(2),
+			"result" => session.engine_color = None,
+			"ping" => {
+				let _ = writeln!(output, "pong {}", arguments);
+			},
+			"quit" => break,
+			_ => {},
+		}
+		let _ = output.flush();
+	}
+}
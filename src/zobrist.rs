@@ -0,0 +1,97 @@
+use crate::{board::{Board, Color, Coordinate}, game::CastlingRights, pieces::{Piece, PieceType}};
+
+struct Keys {
+	pieces: [[[u64; 64]; 6]; 2],
+	black_to_move: u64,
+	// White king side, white queen side, black king side, black queen side
+	castling: [u64; 4],
+	en_passant: [u64; 8],
+}
+
+// Keys are generated at compile time with splitmix64 so hashes are stable between runs
+const fn next_key(state: &mut u64) -> u64 {
+	*state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+	let mut key = *state;
+	key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	key ^ (key >> 31)
+}
+
+const fn generate_keys() -> Keys {
+	let mut state = 0x2545_F491_4F6C_DD1D;
+	let mut keys = Keys {
+		pieces: [[[0; 64]; 6]; 2],
+		black_to_move: 0,
+		castling: [0; 4],
+		en_passant: [0; 8],
+	};
+	let mut color = 0;
+	while color < 2 {
+		let mut piece_type = 0;
+		while piece_type < 6 {
+			let mut square = 0;
+			while square < 64 {
+				keys.pieces[color][piece_type][square] = next_key(&mut state);
+				square += 1;
+			}
+			piece_type += 1;
+		}
+		color += 1;
+	}
+	keys.black_to_move = next_key(&mut state);
+	let mut index = 0;
+	while index < 4 {
+		keys.castling[index] = next_key(&mut state);
+		index += 1;
+	}
+	index = 0;
+	while index < 8 {
+		keys.en_passant[index] = next_key(&mut state);
+		index += 1;
+	}
+	keys
+}
+
+const KEYS: Keys = generate_keys();
+
+pub fn hash(board: &Board) -> u64 {
+	let pieces = board.squares.iter().flatten().fold(0, |hash, square| match &square.piece {
+		Some(piece) => hash ^ piece_key(piece, square.coordinate),
+		None => hash,
+	});
+	pieces ^ state_key(board)
+}
+
+pub(crate) fn piece_key(piece: &Piece, coordinate: Coordinate) -> u64 {
+	KEYS.pieces[piece.color as usize][piece.piece_type as usize][coordinate.rank as usize * 8 + coordinate.file as usize]
+}
+
+// Everything besides piece placement: side to move, castling rights and the en passant file
+pub(crate) fn state_key(board: &Board) -> u64 {
+	let mut key = 0;
+	if board.side_to_move == Color::Black {
+		key ^= KEYS.black_to_move;
+	}
+	for (index, rights) in [board.castling_rights.0, board.castling_rights.1].into_iter().enumerate() {
+		if matches!(rights, CastlingRights::King | CastlingRights::Both) {
+			key ^= KEYS.castling[index * 2];
+		}
+		if matches!(rights, CastlingRights::Queen | CastlingRights::Both) {
+			key ^= KEYS.castling[index * 2 + 1];
+		}
+	}
+	// The en passant file only counts when a pawn is actually placed to make the capture
+	if let Some(passant) = board.can_passant {
+		let behind = match board.side_to_move {
+			Color::White => -1,
+			Color::Black => 1,
+		};
+		let capturable = [-1, 1].into_iter().filter_map(|file_diff| passant.offset(behind, file_diff)).any(|coordinate| {
+			matches!(&board.get_square(coordinate).piece, Some(piece) if piece.color == board.side_to_move && piece.piece_type == PieceType::Pawn)
+		});
+		if capturable {
+			key ^= KEYS.en_passant[passant.file as usize];
+		}
+	}
+	key
+}